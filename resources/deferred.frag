@@ -0,0 +1,71 @@
+#version 330 core
+
+// resolve pass of the deferred path, lights the G-buffer written by gbuffer.frag
+
+in vec2 uv;
+
+out vec4 FragColor;
+
+uniform sampler2D albedoTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D lightTexture;
+
+uniform mat4 view;
+uniform mat4 inverse_view_projection;
+uniform vec3 cameraPosition;
+uniform vec3 cameraFront;
+
+// matches AmbientOcclusion in deferred.rs
+const int AO_BAKED = 0;
+const int AO_SCREEN = 1;
+const int AO_BOTH = 2;
+
+uniform int aoMode;
+
+// matches MAX_POINT_LIGHTS in deferred.rs
+const int MAX_POINT_LIGHTS = 32;
+
+uniform int pointLightCount;
+uniform vec3 pointLightPositions[MAX_POINT_LIGHTS];
+uniform vec3 pointLightColors[MAX_POINT_LIGHTS];
+uniform float pointLightRadii[MAX_POINT_LIGHTS];
+
+#include "include/lighting.glsl"
+#include "include/ssao.glsl"
+
+void main()
+{
+    float depth = texture(depthTexture, uv).r;
+    // the sky was drawn before the chunks and stays as it is
+    if (depth >= 1.0) {
+        discard;
+    }
+
+    vec4 world = inverse_view_projection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    vec3 worldPosition = world.xyz / world.w;
+    vec4 normalAo = texture(normalTexture, uv);
+    vec3 normal = normalize(normalAo.rgb * 2.0 - 1.0);
+    vec4 levels = texture(lightTexture, uv);
+
+    float viewDistance = length(worldPosition - cameraPosition);
+    float viewDepth = dot(worldPosition - cameraPosition, cameraFront);
+    vec3 light = sceneLight(levels.rgb, levels.a, normal, worldPosition, viewDepth);
+
+    for (int i = 0; i < pointLightCount; i++) {
+        vec3 toLight = pointLightPositions[i] - worldPosition;
+        float distance = length(toLight);
+        float falloff = clamp(1.0 - distance / pointLightRadii[i], 0.0, 1.0);
+        light += pointLightColors[i] * falloff * falloff * max(dot(normal, toLight / distance), 0.0);
+    }
+
+    float ao = 1.0;
+    if (aoMode != AO_SCREEN) {
+        ao *= normalAo.a;
+    }
+    if (aoMode != AO_BAKED) {
+        ao *= ssao(viewPosition(uv), normalize(mat3(view) * normal));
+    }
+
+    vec3 color = texture(albedoTexture, uv).rgb * light * ao;
+    FragColor = vec4(mix(color, fogColor, fogAmount(viewDistance)), 1.0);
+}
@@ -0,0 +1,29 @@
+#version 330 core
+
+// geometry pass of the deferred path, shares shader.vert with the forward chunk shader
+
+in vec3 TexCoord;
+flat in vec3 normal;
+in float ao;
+in vec3 blockLight;
+in float skyLight;
+
+// matches the attachments of the G-buffer in deferred.rs
+layout(location = 0) out vec4 albedo;
+layout(location = 1) out vec4 normalAo;
+layout(location = 2) out vec4 lightLevels;
+
+uniform sampler2DArray textureArray;
+
+void main()
+{
+    vec4 color = texture(textureArray, TexCoord);
+    if (color.a < 0.5) {
+        discard;
+    }
+
+    albedo = vec4(color.rgb, 1.0);
+    // the normal comes from the face index, so it only ever points along an axis
+    normalAo = vec4(normal * 0.5 + 0.5, ao);
+    lightLevels = vec4(blockLight, skyLight);
+}
@@ -0,0 +1,77 @@
+// lighting shared by the forward chunk shader and the deferred resolve pass, set by ChunkRenderer::set_lighting_uniforms
+
+// how bright full sky light is at the current time of day
+uniform float skyLightMultiplier;
+
+// matches CASCADES in shadow_map.rs
+const int CASCADES = 3;
+
+uniform sampler2DArrayShadow shadowMaps;
+uniform mat4 lightMatrices[CASCADES];
+uniform float cascadeEnds[CASCADES];
+uniform float cascadeTexelSizes[CASCADES];
+uniform vec3 lightDirection;
+// 0 turns shadows and sun shading off
+uniform float shadowStrength;
+
+// matches FogMode in fog.rs
+const int FOG_NONE = 0;
+const int FOG_LINEAR = 1;
+const int FOG_EXPONENTIAL = 2;
+
+uniform int fogMode;
+uniform float fogStart;
+uniform float fogEnd;
+uniform float fogDensity;
+uniform vec3 fogColor;
+
+// every light level is 80% as bright as the one above it
+vec3 brightness(vec3 level) {
+    return pow(vec3(0.8), (1.0 - level) * 15.0);
+}
+
+// 0 in shadow up to 1 in full sunlight, filtered over 3x3 texels (each one bilinear thanks to the compare sampler)
+float shadowAmount(vec3 worldPosition, vec3 normal, float viewDepth) {
+    int cascade = 0;
+    while (cascade < CASCADES && viewDepth > cascadeEnds[cascade]) {
+        cascade++;
+    }
+    // past the last cascade everything is lit, it is hidden by fog anyway
+    if (cascade == CASCADES) {
+        return 1.0;
+    }
+
+    // moving the lookup along the normal keeps faces from shadowing themselves
+    vec3 offsetPosition = worldPosition + normal * cascadeTexelSizes[cascade] * 1.5;
+    vec4 coords = lightMatrices[cascade] * vec4(offsetPosition, 1.0);
+    coords.xyz = coords.xyz / coords.w * 0.5 + 0.5;
+
+    vec2 texel = 1.0 / vec2(textureSize(shadowMaps, 0).xy);
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadowMaps, vec4(coords.xy + vec2(x, y) * texel, float(cascade), coords.z));
+        }
+    }
+    return lit / 9.0;
+}
+
+// 0 for no fog up to 1 where only the fog colour is left
+float fogAmount(float viewDistance) {
+    switch (fogMode) {
+        case FOG_LINEAR:
+            return clamp((viewDistance - fogStart) / (fogEnd - fogStart), 0.0, 1.0);
+        case FOG_EXPONENTIAL:
+            // squared so it stays clear up close, reaches almost full fog at fogEnd
+            float distance = viewDistance / fogEnd * fogDensity;
+            return 1.0 - exp(-distance * distance);
+    }
+    return 0.0;
+}
+
+// combined block and sky light, sky light is split into ambient and direct light and only the direct part gets shadowed
+vec3 sceneLight(vec3 blockLight, float skyLight, vec3 normal, vec3 worldPosition, float viewDepth) {
+    float direct = shadowStrength > 0.0 ? max(dot(normal, lightDirection), 0.0) * shadowAmount(worldPosition, normal, viewDepth) : 0.0;
+    float skyShade = mix(1.0, 0.6 + 0.4 * direct, shadowStrength);
+    return max(brightness(blockLight), brightness(vec3(skyLight)) * skyLightMultiplier * skyShade);
+}
@@ -0,0 +1,50 @@
+// screen space ambient occlusion shared by the ssao post pass and the deferred resolve pass
+
+uniform sampler2D depthTexture;
+uniform mat4 projection;
+uniform mat4 inverse_projection;
+
+const int ssaoSamples = 16;
+// in blocks
+const float ssaoRadius = 0.75;
+const float ssaoStrength = 1.0;
+
+vec3 viewPosition(vec2 coords) {
+    float depth = texture(depthTexture, coords).r;
+    vec4 position = inverse_projection * vec4(vec3(coords, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// 1 for unoccluded down to 0, position and normal are in view space
+float ssao(vec3 position, vec3 normal) {
+    // random rotation per pixel, the noise gets smoothed out by fxaa and the texture detail
+    vec3 random = normalize(vec3(hash(gl_FragCoord.xy), hash(gl_FragCoord.yx + 7.0), 0.0) * 2.0 - 1.0);
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < ssaoSamples; i++) {
+        // points in the hemisphere around the normal, packed closer to the center
+        float scale = float(i + 1) / float(ssaoSamples);
+        vec3 direction = normalize(vec3(
+            hash(vec2(i, 1.0)) * 2.0 - 1.0,
+            hash(vec2(i, 2.0)) * 2.0 - 1.0,
+            hash(vec2(i, 3.0))
+        ));
+        vec3 samplePosition = position + tbn * direction * ssaoRadius * mix(0.1, 1.0, scale * scale);
+
+        vec4 offset = projection * vec4(samplePosition, 1.0);
+        vec2 sampleUv = offset.xy / offset.w * 0.5 + 0.5;
+        float sceneZ = viewPosition(sampleUv).z;
+
+        // ignore geometry far in front of the sample, it would darken silhouettes
+        float range = smoothstep(0.0, 1.0, ssaoRadius / abs(position.z - sceneZ));
+        occlusion += (sceneZ >= samplePosition.z + 0.02 ? 1.0 : 0.0) * range;
+    }
+
+    return 1.0 - occlusion / float(ssaoSamples) * ssaoStrength;
+}
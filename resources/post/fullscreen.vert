@@ -0,0 +1,11 @@
+#version 330 core
+
+out vec2 uv;
+
+void main()
+{
+    // one triangle covering the whole screen
+    vec2 ndc = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+    uv = ndc * 0.5 + 0.5;
+    gl_Position = vec4(ndc, 0.0, 1.0);
+}
@@ -0,0 +1,51 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 FragColor;
+
+uniform sampler2D inputTexture;
+uniform vec2 texelSize;
+
+const float reduceMin = 1.0 / 128.0;
+const float reduceMul = 1.0 / 8.0;
+const float spanMax = 8.0;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// fxaa without the edge search, blurs along the direction of the local luma gradient
+void main()
+{
+    float lumaNW = luma(texture(inputTexture, uv + vec2(-1.0, -1.0) * texelSize).rgb);
+    float lumaNE = luma(texture(inputTexture, uv + vec2(1.0, -1.0) * texelSize).rgb);
+    float lumaSW = luma(texture(inputTexture, uv + vec2(-1.0, 1.0) * texelSize).rgb);
+    float lumaSE = luma(texture(inputTexture, uv + vec2(1.0, 1.0) * texelSize).rgb);
+    vec3 center = texture(inputTexture, uv).rgb;
+    float lumaM = luma(center);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 direction = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE)
+    );
+    float reduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * reduceMul, reduceMin);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-spanMax), vec2(spanMax)) * texelSize;
+
+    vec3 colorA = 0.5 * (
+        texture(inputTexture, uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(inputTexture, uv + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 colorB = colorA * 0.5 + 0.25 * (
+        texture(inputTexture, uv + direction * -0.5).rgb +
+        texture(inputTexture, uv + direction * 0.5).rgb
+    );
+
+    // the wider blur overshot the local contrast, fall back to the narrow one
+    float lumaB = luma(colorB);
+    FragColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? colorA : colorB, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 FragColor;
+
+uniform sampler2D inputTexture;
+
+const float gamma = 2.2;
+
+void main()
+{
+    vec3 color = texture(inputTexture, uv).rgb;
+    FragColor = vec4(pow(color, vec3(1.0 / gamma)), 1.0);
+}
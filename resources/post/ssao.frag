@@ -0,0 +1,25 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 FragColor;
+
+uniform sampler2D inputTexture;
+
+#include "../include/ssao.glsl"
+
+void main()
+{
+    vec4 color = texture(inputTexture, uv);
+    // nothing to occlude on the sky
+    if (texture(depthTexture, uv).r >= 1.0) {
+        FragColor = color;
+        return;
+    }
+
+    // without a normal buffer the normal comes from how the position changes between pixels
+    vec3 position = viewPosition(uv);
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+
+    FragColor = vec4(color.rgb * ssao(position, normal), color.a);
+}
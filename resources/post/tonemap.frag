@@ -0,0 +1,21 @@
+#version 330 core
+
+in vec2 uv;
+
+out vec4 FragColor;
+
+uniform sampler2D inputTexture;
+uniform float exposure;
+// multiplied into the scene before tonemapping, white unless the camera is under water
+uniform vec3 tint;
+
+// fitted aces filmic curve (Krzysztof Narkowicz)
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main()
+{
+    vec3 color = texture(inputTexture, uv).rgb * tint * exposure;
+    FragColor = vec4(aces(color), 1.0);
+}
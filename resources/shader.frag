@@ -3,13 +3,84 @@
 in vec3 TexCoord;
 in vec3 color;
 
+in vec2 quadPos;
+flat in vec2 quadSize;
+flat in vec3 normal;
+flat in vec3 chunkColor;
+in float ao;
+in vec3 blockLight;
+in float skyLight;
+in float viewDistance;
+in float viewDepth;
+in vec3 worldPosition;
+
 out vec4 FragColor;
 
 uniform sampler2DArray textureArray;
+uniform bool translucentPass;
+#include "include/lighting.glsl"
+
+// matches DebugView in debug_view.rs
+const int VIEW_NONE = 0;
+const int VIEW_FACE_COLORS = 1;
+const int VIEW_CHUNK_COLORS = 2;
+const int VIEW_QUAD_OUTLINES = 3;
+const int VIEW_AMBIENT_OCCLUSION = 4;
+const int VIEW_LIGHT_LEVEL = 5;
+const int VIEW_LEVEL_OF_DETAIL = 6;
+const int VIEW_NORMALS = 7;
+
+uniform int debugView;
+
+// todo: level of details
+const int lod = 0;
+
+const vec3 lodColors[4] = vec3[4](
+    vec3(0.0, 1.0, 0.0),
+    vec3(1.0, 1.0, 0.0),
+    vec3(1.0, 0.5, 0.0),
+    vec3(1.0, 0.0, 0.0)
+);
 
 void main()
 {
-    // Texture lookup using TexCoord
-//    FragColor = texture(textureArray, TexCoord);
-    FragColor = vec4(color, 1);
-}
\ No newline at end of file
+    vec3 light = sceneLight(blockLight, skyLight, normal, worldPosition, viewDepth);
+    float alpha = translucentPass ? 0.5 : 1.0;
+
+    switch (debugView) {
+        case VIEW_FACE_COLORS:
+            FragColor = vec4(color, alpha);
+            return;
+        case VIEW_CHUNK_COLORS:
+            FragColor = vec4(chunkColor, alpha);
+            return;
+        case VIEW_AMBIENT_OCCLUSION:
+            FragColor = vec4(vec3(ao), alpha);
+            return;
+        case VIEW_LIGHT_LEVEL:
+            FragColor = vec4(light, alpha);
+            return;
+        case VIEW_LEVEL_OF_DETAIL:
+            FragColor = vec4(lodColors[lod], alpha);
+            return;
+        case VIEW_NORMALS:
+            FragColor = vec4(normal * 0.5 + 0.5, alpha);
+            return;
+    }
+
+    FragColor = texture(textureArray, TexCoord);
+    // cutout blocks share the opaque pass, their see-through pixels get thrown away instead of blended
+    if (!translucentPass && FragColor.a < 0.5) {
+        discard;
+    }
+    FragColor.rgb *= light * ao;
+    FragColor.rgb = mix(FragColor.rgb, fogColor, fogAmount(viewDistance));
+
+    if (debugView == VIEW_QUAD_OUTLINES) {
+        // distance to the closest quad edge, in pixels so lines stay the same width at any distance
+        vec2 edge = min(quadPos, quadSize - quadPos) / fwidth(quadPos);
+        if (min(edge.x, edge.y) < 1.0) {
+            FragColor = vec4(1.0, 0.0, 0.0, 1.0);
+        }
+    }
+}
@@ -8,7 +8,21 @@ layout(binding = 0, std430) readonly buffer ssbo1 {
 out vec3 TexCoord;
 out vec3 color;
 
+// debug view inputs
+out vec2 quadPos;
+flat out vec2 quadSize;
+flat out vec3 normal;
+flat out vec3 chunkColor;
+out float ao;
+out vec3 blockLight;
+out float skyLight;
+out float viewDistance;
+out float viewDepth;
+out vec3 worldPosition;
+
 uniform mat4 view_projection;
+uniform vec3 cameraPosition;
+uniform vec3 cameraFront;
 
 const vec3 colorLookup[6] = {
     vec3(1.0, 0.0, 1.0),
@@ -19,8 +33,31 @@ const vec3 colorLookup[6] = {
     vec3(0.0, 1.0, 0.0)
 };
 
+const vec3 normalLookup[6] = {
+    vec3(0.0, 1.0, 0.0),
+    vec3(0.0, -1.0, 0.0),
+    vec3(1.0, 0.0, 0.0),
+    vec3(-1.0, 0.0, 0.0),
+    vec3(0.0, 0.0, -1.0),
+    vec3(0.0, 0.0, 1.0)
+};
+
 const int flipLookup[6] = int[6](1, -1, 1, 1, -1, 1);
 
+// matches FLUID_SURFACE_DROP in fluid.rs
+const float FLUID_SURFACE_DROP = 0.125;
+
+// brightness of a corner with 0 to 3 unoccluded neighbours
+const float aoLookup[4] = float[4](0.45, 0.65, 0.82, 1.0);
+
+vec3 hashColor(ivec3 p) {
+    uint hash = uint(p.x) * 73856093u ^ uint(p.y) * 19349663u ^ uint(p.z) * 83492791u;
+    hash ^= hash >> 13u;
+    hash *= 0x5bd1e995u;
+    hash ^= hash >> 15u;
+    return vec3(hash & 0xFFu, (hash >> 8u) & 0xFFu, (hash >> 16u) & 0xFFu) / 255.0;
+}
+
 void main()
 {
     ivec3 chunkOffset = ivec3(
@@ -34,7 +71,9 @@ void main()
     int vertexID = gl_VertexID % 4;
     int index = gl_VertexID >> 2u;
 
-    uint64_t packedData = data[index];
+    // every quad takes two u64s, see pack_data in mesh.rs
+    uint64_t packedData = data[index * 2];
+    uint64_t packedLight = data[index * 2 + 1];
 
     ivec3 vertexPos = ivec3(packedData, packedData >> 6u, packedData >> 12u) & 63;
 
@@ -49,36 +88,52 @@ void main()
     position[wDir] += 0.0007 * flipLookup[face] * (wMod * 2 - 1);
     position[hDir] += 0.0007 * (hMod * 2 - 1);
 
-    uint textureID = uint((packedData >> 30) & 0x0F);
+    // fluid surfaces have their top edge moved down to the height of their level (see surface_height in fluid.rs),
+    // quads with this set are always one block high
+    bool surface = ((packedData >> 46u) & 1u) != 0u;
+    if (surface && (face == 0u || (wDir == 1u && wMod == 1) || (hDir == 1u && hMod == 1))) {
+        int level = int((packedData >> 47u) & 7u);
+        position.y -= 1.0 - (1.0 - FLUID_SURFACE_DROP) * float(8 - level) / 8.0;
+    }
 
-    color = colorLookup[face];
-    // todo: figure out a way to get texCoord with switch statement
-//    switch (face) {
-//        case 0: // Top / 0 b4 4
-//            TexCoord = vec3(position.xz, float(textureID));
-//            color = vec3(1.0, 0.0, 1.0); // magenta
-//            break;
-//        case 1: // Bottom (Black) 1 b4 5
-//            TexCoord = vec3(position.xz, float(textureID));
-//            color = vec3(0.0, 0.0, 0.0);
-//            break;
-//        case 2: // Right / 2 b4 3
-//            TexCoord = vec3(position.zy, float(textureID));
-//            color = vec3(1.0, 1.0, 0.0); // yellow
-//            break;
-//        case 3: // Left / 3 b4 2
-//            TexCoord = vec3(position.zy, float(textureID));
-//            color = vec3(0.0, 0.0, 1.0); // blue
-//            break;
-//        case 4: // Front / 4 b4 0
-//            TexCoord = vec3(position.xy, float(textureID));
-//            color = vec3(1.0, 0.0, 0.0); // red
-//            break;
-//        case 5: // Back / 5 b4 1
-//            TexCoord = vec3(position.xy, float(textureID));
-//            color = vec3(0.0, 1.0, 0.0); // green
-//            break;
-//    }
+    uint textureID = uint((packedData >> 30) & 0xFF);
 
+    color = colorLookup[face];
+    normal = normalLookup[face];
+    chunkColor = hashColor(chunkOffset);
+    quadPos = vec2(w * wMod, h * hMod);
+    quadSize = vec2(w, h);
+
+    // vertexID is the corner index the mesher sampled ao and light for
+    ao = aoLookup[uint(packedData >> (38 + vertexID * 2)) & 3u];
+    uint cornerLight = uint(packedLight >> (vertexID * 16)) & 0xFFFFu;
+    blockLight = vec3(cornerLight & 15u, (cornerLight >> 4u) & 15u, (cornerLight >> 8u) & 15u) / 15.0;
+    skyLight = float((cornerLight >> 12u) & 15u) / 15.0;
+
+    // uvs come from the world position so textures repeat once per block across greedy merged quads,
+    // side faces use -y so that the top of a texture faces up
+    vec3 uvPos = vec3(vertexPos);
+    switch (face) {
+        case 0: // Top
+        case 1: // Bottom
+            TexCoord = vec3(uvPos.x, uvPos.z, float(textureID));
+            break;
+        case 2: // Right
+            TexCoord = vec3(-uvPos.z, -uvPos.y, float(textureID));
+            break;
+        case 3: // Left
+            TexCoord = vec3(uvPos.z, -uvPos.y, float(textureID));
+            break;
+        case 4: // Front
+            TexCoord = vec3(-uvPos.x, -uvPos.y, float(textureID));
+            break;
+        case 5: // Back
+            TexCoord = vec3(uvPos.x, -uvPos.y, float(textureID));
+            break;
+    }
+
+    worldPosition = position;
+    viewDistance = length(position - cameraPosition);
+    viewDepth = dot(position - cameraPosition, cameraFront);
     gl_Position = view_projection * vec4(position, 1.0);
 }
@@ -0,0 +1,15 @@
+#version 330 core
+
+// depth only pass for the shadow maps, shares shader.vert with the chunk shader
+
+in vec3 TexCoord;
+
+uniform sampler2DArray textureArray;
+
+void main()
+{
+    // see-through pixels of cutout blocks don't cast shadows
+    if (texture(textureArray, TexCoord).a < 0.5) {
+        discard;
+    }
+}
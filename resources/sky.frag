@@ -0,0 +1,39 @@
+#version 330 core
+
+in vec2 ndc;
+
+out vec4 FragColor;
+
+uniform mat4 inverse_view_projection;
+
+uniform vec3 zenithColor;
+uniform vec3 horizonColor;
+uniform vec3 sunDirection;
+uniform vec3 moonDirection;
+uniform float daylight;
+
+const vec3 sunColor = vec3(1.0, 0.95, 0.8);
+const vec3 moonColor = vec3(0.8, 0.85, 0.95);
+
+// sizes as the cosine of the angle from the centre
+const float sunSize = 0.9995;
+const float moonSize = 0.9997;
+
+void main()
+{
+    vec4 far = inverse_view_projection * vec4(ndc, 1.0, 1.0);
+    vec3 direction = normalize(far.xyz / far.w);
+
+    // below the horizon keeps the horizon colour so there is no hard line at the edge of the world
+    float height = max(direction.y, 0.0);
+    vec3 color = mix(horizonColor, zenithColor, pow(height, 0.5));
+
+    float sun = dot(direction, sunDirection);
+    color += sunColor * pow(max(sun, 0.0), 256.0) * 0.5;
+    color = mix(color, sunColor, smoothstep(sunSize - 0.0001, sunSize, sun));
+
+    float moon = dot(direction, moonDirection);
+    color = mix(color, moonColor, smoothstep(moonSize - 0.0001, moonSize, moon) * (1.0 - daylight * 0.7));
+
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,59 @@
+#version 330 core
+
+// surface of fluid blocks, drawn with shader.vert after the opaque faces and blended over them
+
+in vec3 TexCoord;
+flat in vec3 normal;
+in float ao;
+in vec3 blockLight;
+in float skyLight;
+in float viewDistance;
+in float viewDepth;
+in vec3 worldPosition;
+
+out vec4 FragColor;
+
+uniform sampler2DArray textureArray;
+uniform vec3 cameraPosition;
+// seconds, only used to animate the surface
+uniform float animationTime;
+
+#include "include/lighting.glsl"
+
+// how see-through the water is when looking straight at it, it gets more opaque at grazing angles
+const float MIN_ALPHA = 0.6;
+const float MAX_ALPHA = 0.9;
+
+// small waves on the surface, only used for the shading so the geometry stays flat
+vec3 waveNormal(vec3 position) {
+    float t = animationTime;
+    float dx = cos(position.x * 1.7 + t * 1.3) * 0.6 + cos((position.x + position.z) * 2.9 - t * 2.1) * 0.3;
+    float dz = cos(position.z * 1.9 - t * 1.1) * 0.6 + cos((position.x - position.z) * 2.3 + t * 1.7) * 0.3;
+    return normalize(vec3(dx * 0.08, 1.0, dz * 0.08));
+}
+
+void main()
+{
+    // two copies of the texture drifting in different directions
+    vec2 drift = vec2(animationTime * 0.05, animationTime * 0.03);
+    vec4 first = texture(textureArray, vec3(TexCoord.xy + drift, TexCoord.z));
+    vec4 second = texture(textureArray, vec3(TexCoord.xy * 0.75 - drift.yx, TexCoord.z));
+    vec3 albedo = mix(first.rgb, second.rgb, 0.5);
+
+    vec3 surfaceNormal = normal.y > 0.5 ? waveNormal(worldPosition) : normal;
+    vec3 light = sceneLight(blockLight, skyLight, normal, worldPosition, viewDepth);
+    vec3 color = albedo * light * ao;
+
+    vec3 toCamera = normalize(cameraPosition - worldPosition);
+    float facing = abs(dot(toCamera, surfaceNormal));
+    float alpha = mix(MAX_ALPHA, MIN_ALPHA, facing);
+
+    // reflection of the sun or moon, only where the sky is visible and the light isn't blocked
+    vec3 reflected = reflect(-toCamera, surfaceNormal);
+    float highlight = pow(max(dot(reflected, lightDirection), 0.0), 96.0) * shadowStrength * skyLight;
+    highlight *= shadowAmount(worldPosition, normal, viewDepth);
+    color += vec3(highlight) * skyLightMultiplier;
+    alpha = min(alpha + highlight, 1.0);
+
+    FragColor = vec4(mix(color, fogColor, fogAmount(viewDistance)), alpha);
+}
@@ -1,9 +1,7 @@
 extern crate glfw;
 
-use std::cmp::PartialEq;
 use std::time::Instant;
 
-use fastnoise_lite::{FastNoiseLite, NoiseType};
 use gl::{DEPTH_TEST};
 use glfw::{Action, Context, GlfwReceiver, Key, Window, WindowEvent};
 use ultraviolet::{Vec3};
@@ -11,14 +9,28 @@ use ultraviolet::{Vec3};
 use crate::render::camera::Camera;
 use crate::render::camera::CameraMovement::{BACKWARD, DOWN, FORWARD, LEFT, RIGHT, UP};
 use crate::render::chunk_renderer::{ChunkRenderer};
+use crate::render::deferred::PointLight;
+use crate::render::fog::UNDERWATER_TINT;
+use crate::render::post_processing::PostProcessing;
 use crate::render::shaders::Shader;
+use crate::render::sky_renderer::SkyRenderer;
 use crate::render::textures::texture_array::TextureArray;
-use crate::world::chunk::mesh::greedy_mesh;
-use crate::world::world::{make_example_chunks, World};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::ChunkPosition;
+use crate::world::generator::terrain::{GeneratorSettings, GeneratorType};
+use crate::world::settings::WorldSettings;
+use crate::world::time::{MIDNIGHT, NOON, SUNRISE, SUNSET};
+use crate::world::streaming::ChunkStreamer;
+use crate::world::world::World;
 
 mod render;
 mod world;
 
+/// columns of chunks the streamer may generate per frame
+const COLUMNS_PER_FRAME: usize = 2;
+/// chunks that may get meshed per frame
+const MAX_UPLOADS_PER_FRAME: usize = 64;
+
 fn main() {
     use glfw::fail_on_errors;
     let mut glfw = glfw::init(fail_on_errors!()).unwrap();
@@ -51,7 +63,8 @@ fn main() {
     glfw.set_swap_interval(glfw::SwapInterval::None);
 
 
-    let mut camera = Camera::create(Vec3::new(0.0, 40.0, 0.0), 90.0, 0.0);
+    let (width, height) = window.get_framebuffer_size();
+    let mut camera = Camera::create(Vec3::new(0.0, 40.0, 0.0), 90.0, 0.0, width as f32 / height as f32);
 
     let mut first_mouse = true;
     let mut last_x: f32 = 1920.0 / 2.0;
@@ -62,14 +75,25 @@ fn main() {
     let mut delta_time: f32;
     let mut last_frame: f32 = 0.0;
 
-    let mut world = World::new();
-
-    let noise = {
-        let mut noise = FastNoiseLite::with_seed(8008135);
-        noise.set_noise_type(Some(NoiseType::Perlin));
-        noise
+    // the first argument is the name of the world, it is opened if it was saved before, otherwise it's created with a
+    // random seed and the generator picked by the second argument, either by name or with its settings, see `GeneratorSettings::parse`
+    let mut args = std::env::args().skip(1);
+    let name = args.next().unwrap_or_else(|| String::from("world"));
+    let settings = match WorldSettings::load(&name) {
+        Some(settings) => { settings.unwrap_or_else(|err| panic!("{}", err)) }
+        None => {
+            let generator_settings = match args.next() {
+                None => { GeneratorType::Fractal.default_settings() }
+                Some(argument) => { GeneratorSettings::parse(&argument).unwrap_or_else(|err| panic!("{}", err)) }
+            };
+            let settings = WorldSettings::new(&name, generator_settings);
+            settings.save().unwrap_or_else(|err| panic!("{}", err));
+            settings
+        }
     };
-    make_example_chunks(&mut world, &noise);
+
+    let mut world = World::new(BlockRegistry::load("resources/blocks.ron"), settings);
+    let generator = world.settings.create_generator(&world.registry);
 
     let mut chunk_renderer = unsafe {
         ChunkRenderer::create(
@@ -77,32 +101,50 @@ fn main() {
                 "resources/shader.vert",
                 "resources/shader.frag",
             ),
+            Shader::new(
+                "resources/shader.vert",
+                "resources/gbuffer.frag",
+            ),
+            Shader::new(
+                "resources/shader.vert",
+                "resources/water.frag",
+            ),
+            Shader::new(
+                "resources/shader.vert",
+                "resources/shadow.frag",
+            ),
+            Shader::new(
+                "resources/post/fullscreen.vert",
+                "resources/deferred.frag",
+            ),
             TextureArray::create(
-                vec![
-                    "resources/textures/dirt.png",
-                    "resources/textures/cobblestone.png",
-                ],
+                world.registry.texture_paths().iter().map(|it| it.as_str()).collect(),
                 16,
                 16,
             ),
         )
     };
 
-    unsafe {
-        for (pos, mut chunk) in &mut world.chunks {
-            let mut index = 0;
-            for vertices in greedy_mesh(&chunk) {
-                if vertices.len() == 0 { continue; };
-                // NOTE: Might be issue with negative numbers
-                let base_instance = ((pos.x  & 0x7FF) << 21) | ((pos.y & 0x7F) << 14) | ((pos.z & 0x7FF) << 3) | index;
-                let command = chunk_renderer.get_draw_command(vertices.len() as u32, base_instance as u32);
-
-                chunk_renderer.upload_mesh(&command, vertices);
-                chunk.add_draw_command(command);
-                index += 1;
-            }
-        }
-    }
+    // everything in view gets loaded up front, after that a few columns per frame as the camera moves
+    let mut streamer = ChunkStreamer::new(generator, world.settings.create_decorator(&world.registry), chunk_renderer.render_distance + 1);
+    streamer.update(&mut world, camera_chunk(&camera), usize::MAX);
+    camera.position.y = camera.position.y.max(world.height_at(0, 0) as f32 + 8.0);
+
+    let sky_renderer = unsafe {
+        SkyRenderer::create(Shader::new(
+            "resources/sky.vert",
+            "resources/sky.frag",
+        ))
+    };
+
+    let mut post_processing = unsafe {
+        let (width, height) = window.get_framebuffer_size();
+        PostProcessing::create(width, height)
+    };
+    post_processing.add_pass("ssao", "resources/post/ssao.frag", false);
+    post_processing.add_pass("tonemap", "resources/post/tonemap.frag", true);
+    post_processing.add_pass("gamma", "resources/post/gamma.frag", true);
+    post_processing.add_pass("fxaa", "resources/post/fxaa.frag", true);
 
     unsafe {
         gl::Enable(DEPTH_TEST);
@@ -119,7 +161,30 @@ fn main() {
 
         if now.duration_since(last_update).as_secs_f32() >= 1.0 {
             last_update = now;
-            window.set_title(&format!("FPS: {}, press r for wireframe", frame_count));
+            let (hours, minutes) = world.time.clock();
+            let passes: Vec<&str> = post_processing.passes.iter()
+                .filter(|pass| pass.enabled)
+                .map(|pass| pass.name)
+                .collect();
+            window.set_title(&format!(
+                "FPS: {}, {} ({} world) in {}, press r for wireframe, f to cycle debug views ({}), g to cycle fog ({}), h to toggle shadows, render distance {} ([/]), time {:02}:{:02} x{}{} (p pause, -/+ speed, 1-4 set), {} block updates (v pour water), post processing [{}] (f1-f4), {} shading (m) with {} ao (o), {} point lights (l add, k clear)",
+                frame_count,
+                world.settings.name,
+                world.settings.generator.generator_type().name(),
+                world.biome_at(camera.position.x.floor() as i32, camera.position.z.floor() as i32).name(),
+                chunk_renderer.debug_view.name(),
+                chunk_renderer.fog.mode.name(),
+                chunk_renderer.render_distance,
+                hours,
+                minutes,
+                world.time.speed,
+                if world.time.paused { " paused" } else { "" },
+                world.pending_updates(),
+                passes.join(", "),
+                chunk_renderer.shading.name(),
+                chunk_renderer.deferred.ambient_occlusion.name(),
+                chunk_renderer.deferred.point_lights.len()
+            ));
             frame_count = 0
         }
 
@@ -130,16 +195,42 @@ fn main() {
         // input
 
         process_input(&mut window, &mut camera, delta_time);
-        process_events(&events, &mut first_mouse, &mut last_x, &mut last_y, &mut camera, &mut wireframe);
+        process_events(&events, &mut first_mouse, &mut last_x, &mut last_y, &mut camera, &mut wireframe, &mut chunk_renderer, &mut post_processing, &mut world);
+
+        world.time.update(delta_time);
+        let center = camera_chunk(&camera);
+        world.simulation_center = center;
+        world.update(delta_time);
+
+        streamer.distance = chunk_renderer.render_distance + 1;
+        for mut chunk in streamer.update(&mut world, center, COLUMNS_PER_FRAME) {
+            chunk_renderer.free_chunk(&mut chunk);
+        }
+
+        let underwater = world.is_submerged(camera.position);
+        chunk_renderer.underwater = underwater;
+        post_processing.tint = if underwater { UNDERWATER_TINT } else { Vec3::one() };
 
 
         // render
 
         unsafe {
-            gl::ClearColor(0.0, 2.0, 1.0, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            // closest first, whatever doesn't fit into this frame waits for the next one
+            let mut dirty: Vec<ChunkPosition> = world.chunks.iter()
+                .filter(|(_, chunk)| chunk.dirty)
+                .map(|(pos, _)| *pos)
+                .collect();
+            dirty.sort_unstable_by_key(|pos| (pos.x - center.x).pow(2) + (pos.y - center.y).pow(2) + (pos.z - center.z).pow(2));
+            for pos in dirty.into_iter().take(MAX_UPLOADS_PER_FRAME) {
+                chunk_renderer.upload_chunk(&pos, &mut world);
+            }
+
+            post_processing.begin();
 
-            chunk_renderer.render(&world.chunks, &camera)
+            sky_renderer.render(&camera, &world.time);
+            chunk_renderer.render(&world.chunks, &camera, &world.time);
+
+            post_processing.finish(&camera);
         }
 
         window.swap_buffers();
@@ -171,6 +262,7 @@ fn process_input(window: &mut Window, camera: &mut Camera, delta: f32) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn process_events(
     events: &GlfwReceiver<(f64, WindowEvent)>,
     first_mouse: &mut bool,
@@ -178,15 +270,79 @@ pub fn process_events(
     last_y: &mut f32,
     camera: &mut Camera,
     wireframe: &mut bool,
+    chunk_renderer: &mut ChunkRenderer,
+    post_processing: &mut PostProcessing,
+    world: &mut World,
 ) {
     for (_, event) in glfw::flush_messages(events) {
         match event {
-            WindowEvent::FramebufferSize(width, height) => unsafe {
-                gl::Viewport(0, 0, width, height)
+            // a minimised window has no size, its targets are kept until it comes back
+            WindowEvent::FramebufferSize(width, height) if width > 0 && height > 0 => unsafe {
+                gl::Viewport(0, 0, width, height);
+                post_processing.resize(width, height);
+                camera.aspect_ratio = width as f32 / height as f32;
             }
             WindowEvent::Key(Key::R, _, Action::Press, _) => unsafe {
                 toggle_wireframe(wireframe);
             }
+            WindowEvent::Key(Key::F, _, Action::Press, _) => {
+                chunk_renderer.debug_view = chunk_renderer.debug_view.next();
+            }
+            WindowEvent::Key(Key::H, _, Action::Press, _) => {
+                chunk_renderer.shadow_map.enabled = !chunk_renderer.shadow_map.enabled;
+            }
+            WindowEvent::Key(Key::M, _, Action::Press, _) => {
+                chunk_renderer.shading = chunk_renderer.shading.next();
+            }
+            WindowEvent::Key(Key::O, _, Action::Press, _) => {
+                let deferred = &mut chunk_renderer.deferred;
+                deferred.ambient_occlusion = deferred.ambient_occlusion.next();
+            }
+            WindowEvent::Key(Key::L, _, Action::Press, _) => {
+                // cycles through a few colours so lights next to each other can be told apart
+                let colors = [Vec3::new(1.0, 0.6, 0.3), Vec3::new(0.3, 0.6, 1.0), Vec3::new(0.4, 1.0, 0.4), Vec3::new(1.0, 0.3, 0.8)];
+                let deferred = &mut chunk_renderer.deferred;
+                deferred.add_point_light(PointLight {
+                    position: camera.position,
+                    color: colors[deferred.point_lights.len() % colors.len()],
+                    radius: 16.0,
+                });
+            }
+            WindowEvent::Key(Key::K, _, Action::Press, _) => {
+                chunk_renderer.deferred.point_lights.clear();
+            }
+            WindowEvent::Key(Key::F1, _, Action::Press, _) => { post_processing.toggle_pass(0) }
+            WindowEvent::Key(Key::F2, _, Action::Press, _) => { post_processing.toggle_pass(1) }
+            WindowEvent::Key(Key::F3, _, Action::Press, _) => { post_processing.toggle_pass(2) }
+            WindowEvent::Key(Key::F4, _, Action::Press, _) => { post_processing.toggle_pass(3) }
+            WindowEvent::Key(Key::G, _, Action::Press, _) => {
+                chunk_renderer.cycle_fog();
+            }
+            WindowEvent::Key(Key::LeftBracket, _, Action::Press | Action::Repeat, _) => {
+                chunk_renderer.decrease_render_distance();
+            }
+            WindowEvent::Key(Key::RightBracket, _, Action::Press | Action::Repeat, _) => {
+                chunk_renderer.increase_render_distance();
+            }
+            WindowEvent::Key(Key::P, _, Action::Press, _) => {
+                world.time.paused = !world.time.paused;
+            }
+            WindowEvent::Key(Key::Equal, _, Action::Press | Action::Repeat, _) => {
+                world.time.speed_up();
+            }
+            WindowEvent::Key(Key::Minus, _, Action::Press | Action::Repeat, _) => {
+                world.time.slow_down();
+            }
+            WindowEvent::Key(Key::V, _, Action::Press, _) => {
+                // a water source where the camera is
+                let (x, y, z) = (camera.position.x.floor() as i32, camera.position.y.floor() as i32, camera.position.z.floor() as i32);
+                let water = world.registry.get("water");
+                world.set_block(x, y, z, water);
+            }
+            WindowEvent::Key(Key::Num1, _, Action::Press, _) => { world.time.set(SUNRISE) }
+            WindowEvent::Key(Key::Num2, _, Action::Press, _) => { world.time.set(NOON) }
+            WindowEvent::Key(Key::Num3, _, Action::Press, _) => { world.time.set(SUNSET) }
+            WindowEvent::Key(Key::Num4, _, Action::Press, _) => { world.time.set(MIDNIGHT) }
             WindowEvent::CursorPos(x, y) => {
                 let (x, y) = (x as f32, y as f32);
                 if *first_mouse {
@@ -216,4 +372,8 @@ unsafe fn toggle_wireframe(value: &mut bool) {
         *value = true;
         gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
     }
-}
\ No newline at end of file
+}
+
+fn camera_chunk(camera: &Camera) -> ChunkPosition {
+    ChunkPosition::containing(camera.position.x.floor() as i32, camera.position.y.floor() as i32, camera.position.z.floor() as i32)
+}
@@ -1,7 +1,13 @@
 use std::cmp::PartialEq;
 use ultraviolet::{Mat4, Vec3};
+use ultraviolet::projection::perspective_gl;
 use self::CameraMovement::*;
 
+/// vertical field of view in degrees
+pub const FOV: f32 = 45.0;
+pub const NEAR_PLANE: f32 = 0.1;
+pub const FAR_PLANE: f32 = 10000.0;
+
 #[derive(PartialEq)]
 pub enum CameraMovement {
     FORWARD,
@@ -21,12 +27,14 @@ pub struct Camera {
     pub up: Vec3,
     pub right: Vec3,
     pub world_up: Vec3,
+    /// width over height of the framebuffer, changes when the window is resized
+    pub aspect_ratio: f32,
     //
     // pub perspective: Mat4,
 }
 
 impl Camera {
-    pub fn create(position: Vec3, yaw: f32, pitch: f32) -> Camera {
+    pub fn create(position: Vec3, yaw: f32, pitch: f32, aspect_ratio: f32) -> Camera {
         let mut camera = Camera {
             position,
             yaw,
@@ -35,6 +43,7 @@ impl Camera {
             up: Vec3::zero(),
             right: Vec3::zero(),
             world_up: Vec3::unit_y(),
+            aspect_ratio,
             // perspective: Mat4::identity(),
         };
         camera.update_vectors();
@@ -45,6 +54,10 @@ impl Camera {
         Mat4::look_at(self.position, self.position + self.front, self.up)
     }
 
+    pub fn projection_matrix(&self) -> Mat4 {
+        perspective_gl(FOV.to_radians(), self.aspect_ratio, NEAR_PLANE, FAR_PLANE)
+    }
+
     fn update_vectors(&mut self) {
         let direction = Vec3::new(
             self.yaw.to_radians().cos() * self.pitch.to_radians().cos(),
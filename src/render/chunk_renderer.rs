@@ -1,19 +1,33 @@
 use std::collections::HashMap;
 use std::ffi::c_void;
 use std::ptr;
-use gl::{DRAW_INDIRECT_BUFFER, DYNAMIC_DRAW, DYNAMIC_STORAGE_BIT, ELEMENT_ARRAY_BUFFER, SHADER_STORAGE_BUFFER, TEXTURE_2D_ARRAY, TRIANGLES, UNSIGNED_INT};
+use std::time::Instant;
+use gl::{BLEND, DRAW_FRAMEBUFFER_BINDING, DRAW_INDIRECT_BUFFER, DYNAMIC_DRAW, DYNAMIC_STORAGE_BIT, ELEMENT_ARRAY_BUFFER, FALSE, FRAMEBUFFER, ONE_MINUS_SRC_ALPHA, POLYGON_OFFSET_FILL, SHADER_STORAGE_BUFFER, SRC_ALPHA, TEXTURE0, TEXTURE1, TRIANGLES, TRUE, UNSIGNED_INT, VIEWPORT};
 use gl::types::{GLintptr, GLsizei, GLsizeiptr};
-use ultraviolet::projection::perspective_gl;
 use ultraviolet::Vec3;
 use crate::render::camera::Camera;
+use crate::render::debug_view::DebugView;
+use crate::render::deferred::{DeferredRenderer, Shading};
+use crate::render::fog::{Fog, FogMode, UNDERWATER_FOG_COLOR, UNDERWATER_FOG_DENSITY, UNDERWATER_FOG_END};
 use crate::render::frustum::Frustum;
+use crate::render::shadow_map::ShadowMap;
 use crate::render::shaders::Shader;
 use crate::render::textures::texture_array::TextureArray;
+use crate::world::block::block::Block;
 use crate::world::chunk::chunk::{Chunk, CS, ChunkPosition, CS_F32};
+use crate::world::chunk::mesh::{greedy_mesh, QUAD_WORDS};
+use crate::world::streaming::{MAX_CHUNK_Y, MIN_CHUNK_Y};
+use crate::world::time::WorldTime;
+use crate::world::world::World;
 
 const BUFFER_SIZE: u32 = 500_000_000;
 const MAX_DRAW_COMMANDS: usize = 100_000;
-const QUAD_SIZE_BYTES: u32 = 8;
+const DEFAULT_RENDER_DISTANCE: i32 = 16;
+/// Memory the loaded chunks may take up at most, the render distance is capped so it holds
+const CHUNK_MEMORY_BUDGET: usize = 4 << 30;
+const MAX_RENDER_DISTANCE: i32 = max_render_distance();
+const _: () = assert!(MAX_RENDER_DISTANCE >= DEFAULT_RENDER_DISTANCE, "the default render distance doesn't fit the memory budget");
+const QUAD_SIZE_BYTES: u32 = (QUAD_WORDS * size_of::<u64>()) as u32;
 
 
 #[repr(C)]
@@ -31,32 +45,138 @@ struct BufferSlot {
     size_byes: u32,
 }
 
+/// Hands out space in the chunk buffer. Freed slots are kept sorted by where they start and merged with the free
+/// slots right next to them, so loading and unloading chunks over and over doesn't cut the buffer into tiny pieces
+struct BufferAllocator {
+    size_bytes: u32,
+    allocation_end: u32,
+    free_slots: Vec<BufferSlot>,
+}
+
+impl BufferAllocator {
+    fn new(size_bytes: u32) -> BufferAllocator {
+        BufferAllocator {
+            size_bytes,
+            allocation_end: 0,
+            free_slots: vec![],
+        }
+    }
+
+    /// `None` if there is no free space left that is big enough
+    fn allocate(&mut self, requested_size: u32) -> Option<BufferSlot> {
+        // best fit out of the freed slots, whatever is left over stays free
+        let best_fit = self.free_slots.iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.size_byes >= requested_size)
+            .min_by_key(|(_, slot)| slot.size_byes)
+            .map(|(index, _)| index);
+
+        if let Some(index) = best_fit {
+            let free = &mut self.free_slots[index];
+            let slot = BufferSlot {
+                start_bytes: free.start_bytes,
+                size_byes: requested_size,
+            };
+            free.start_bytes += requested_size;
+            free.size_byes -= requested_size;
+            if free.size_byes == 0 {
+                self.free_slots.remove(index);
+            }
+            return Some(slot);
+        }
+
+        if (self.size_bytes - self.allocation_end) < requested_size {
+            return None;
+        }
+        let slot = BufferSlot {
+            start_bytes: self.allocation_end,
+            size_byes: requested_size,
+        };
+        self.allocation_end += requested_size;
+        Some(slot)
+    }
+
+    fn free(&mut self, slot: BufferSlot) {
+        let index = self.free_slots.partition_point(|free| free.start_bytes < slot.start_bytes);
+        let mut slot = slot;
+
+        if index < self.free_slots.len() && slot.start_bytes + slot.size_byes == self.free_slots[index].start_bytes {
+            slot.size_byes += self.free_slots.remove(index).size_byes;
+        }
+        if index > 0 {
+            let previous = &mut self.free_slots[index - 1];
+            if previous.start_bytes + previous.size_byes == slot.start_bytes {
+                previous.size_byes += slot.size_byes;
+                if previous.start_bytes + previous.size_byes == self.allocation_end {
+                    self.allocation_end = previous.start_bytes;
+                    self.free_slots.pop();
+                }
+                return;
+            }
+        }
+
+        // the end of the allocated space gets handed back instead of being kept as a free slot
+        if slot.start_bytes + slot.size_byes == self.allocation_end {
+            self.allocation_end = slot.start_bytes;
+        } else {
+            self.free_slots.insert(index, slot);
+        }
+    }
+}
+
 pub struct ChunkRenderer {
     vao: u32,
     ibo: u32,
     ssbo: u32,
     command_buffer: u32,
 
-    allocation_end: u32,
-    used_slots: Vec<BufferSlot>,
+    allocator: BufferAllocator,
     draw_commands: Vec<DrawElementsIndirectCommand>,
 
     shader: Shader,
+    /// writes the opaque faces into the G-buffer when shading is deferred
+    gbuffer_shader: Shader,
+    /// animated surface of fluids, drawn before the other translucent faces
+    water_shader: Shader,
+    /// depth only variant of `shader` for the shadow maps
+    shadow_shader: Shader,
     texture_array: TextureArray,
+    pub shadow_map: ShadowMap,
+    pub deferred: DeferredRenderer,
+    pub shading: Shading,
+
+    pub debug_view: DebugView,
+    pub fog: Fog,
+    /// in chunks, nothing further away than this is drawn
+    pub render_distance: i32,
+    /// the camera is inside a fluid, fog gets replaced by thick underwater fog
+    pub underwater: bool,
+    /// fluid surfaces are animated relative to this
+    start: Instant,
 }
 
 impl ChunkRenderer {
-    pub unsafe fn create(shader: Shader, texture_array: TextureArray) -> ChunkRenderer {
+    pub unsafe fn create(shader: Shader, gbuffer_shader: Shader, water_shader: Shader, shadow_shader: Shader, resolve_shader: Shader, texture_array: TextureArray) -> ChunkRenderer {
         let mut renderer = ChunkRenderer {
             vao: 0,
             ibo: 0,
             ssbo: 0,
             command_buffer: 0,
-            allocation_end: 0,
-            used_slots: vec![],
+            allocator: BufferAllocator::new(BUFFER_SIZE),
             draw_commands: vec![],
             shader,
-            texture_array
+            gbuffer_shader,
+            water_shader,
+            shadow_shader,
+            texture_array,
+            shadow_map: ShadowMap::create(),
+            deferred: DeferredRenderer::create(resolve_shader),
+            shading: Shading::Forward,
+            debug_view: DebugView::None,
+            fog: Fog::new(),
+            render_distance: DEFAULT_RENDER_DISTANCE,
+            underwater: false,
+            start: Instant::now(),
         };
 
         gl::GenVertexArrays(1, &mut renderer.vao);
@@ -91,63 +211,189 @@ impl ChunkRenderer {
         renderer
     }
 
-    pub unsafe fn render(&mut self, chunks: &HashMap<ChunkPosition, Chunk>, camera: &Camera) {
-        self.shader.use_program();
-        self.texture_array.bind(TEXTURE_2D_ARRAY);
-        self.shader.set_int("textureArray", 0);
+    pub unsafe fn render(&mut self, chunks: &HashMap<ChunkPosition, Chunk>, camera: &Camera, time: &WorldTime) {
+        let fog_end = self.fog.end(self.render_distance);
+        self.texture_array.bind(TEXTURE0);
 
-        let view_projection = perspective_gl(45f32.to_radians(), 1920.0 / 1080.0, 0.1, 10000.0) * camera.view_matrix();
-        self.shader.set_mat4("view_projection", &view_projection);
+        if self.shadow_map.enabled {
+            self.render_shadows(chunks, camera, time.light_direction(), fog_end);
+        }
+
+        self.shadow_map.bind_texture(TEXTURE1);
+
+        let view_projection = camera.projection_matrix() * camera.view_matrix();
         let frustum = Frustum::create(view_projection);
 
+        // chunks with translucent or fluid faces, sorted back to front after the opaque pass
+        let mut translucent_chunks: Vec<(f32, &Chunk)> = Vec::new();
+
+        // distance from the chunk center at which all of the chunk is past the render distance
+        let max_distance = fog_end + CS_F32 * 3f32.sqrt() / 2.0;
+
         for (pos, chunk) in chunks {
-            let pos = pos.to_vec3();
-            let world_pos = pos * CS_F32;
+            let world_pos = pos.world_pos();
+            let center = world_pos + Vec3::broadcast(CS_F32 / 2.0);
+
+            if (center - camera.position).mag() > max_distance {
+                continue;
+            }
 
             // todo: gpu frustum and occlusion culling
             if !frustum.test_aabb(world_pos, world_pos + Vec3::broadcast(CS_F32)) {
                 continue;
             }
 
-            let mut index = 0;
             for command in &chunk.draw_commands {
-                match index {
-                    0 => {
-                        if (camera.position.y / CS_F32).floor() >= pos.y {
-                            self.draw_commands.push(command.clone());
-                        }
-                    }
-                    1 => {
-                        if (camera.position.y / CS_F32).floor() <= pos.y {
-                            self.draw_commands.push(command.clone());
-                        }
-                    }
-                    2 => {
-                        if (camera.position.x / CS_F32).floor() >= pos.x {
-                            self.draw_commands.push(command.clone());
-                        }
-                    }
-                    3 => {
-                        if (camera.position.x / CS_F32).floor() <= pos.x {
-                            self.draw_commands.push(command.clone());
-                        }
-                    }
-                    4 => {
-                        if (camera.position.z / CS_F32).floor() <= pos.z {
-                            self.draw_commands.push(command.clone());
-                        }
-                    }
-                    5 => {
-                        if (camera.position.z / CS_F32).floor() >= pos.z {
-                            self.draw_commands.push(command.clone());
-                        }
-                    }
-                    _ => {}
+                if is_face_visible(command, pos, camera) {
+                    self.draw_commands.push(command.clone());
+                }
+            }
+
+            if !chunk.translucent_draw_commands.is_empty() || !chunk.fluid_draw_commands.is_empty() {
+                translucent_chunks.push(((center - camera.position).mag_sq(), chunk));
+            }
+        }
+
+        // the debug views only exist in the forward shader
+        let deferred = self.shading == Shading::Deferred && self.debug_view == DebugView::None;
+        if deferred {
+            self.deferred.begin_geometry();
+            self.gbuffer_shader.use_program();
+            self.gbuffer_shader.set_int("textureArray", 0);
+            self.gbuffer_shader.set_vec3("cameraPosition", &camera.position);
+            self.gbuffer_shader.set_vec3("cameraFront", &camera.front);
+            self.gbuffer_shader.set_mat4("view_projection", &view_projection);
+            self.draw();
+
+            let resolve_shader = self.deferred.resolve_shader();
+            resolve_shader.use_program();
+            self.set_lighting_uniforms(resolve_shader, camera, time, fog_end);
+            self.deferred.resolve(camera);
+        }
+
+        self.shader.use_program();
+        self.shader.set_int("textureArray", 0);
+        self.shader.set_int("debugView", self.debug_view as i32);
+        self.shader.set_mat4("view_projection", &view_projection);
+        self.set_lighting_uniforms(&self.shader, camera, time, fog_end);
+
+        if !deferred {
+            self.shader.set_bool("translucentPass", false);
+            self.draw();
+        }
+
+        translucent_chunks.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        gl::Enable(BLEND);
+        gl::BlendFunc(SRC_ALPHA, ONE_MINUS_SRC_ALPHA);
+        gl::DepthMask(FALSE);
+
+        for (_, chunk) in &translucent_chunks {
+            self.draw_commands.extend(chunk.fluid_draw_commands.iter().cloned());
+        }
+        // with a debug view on fluids are drawn by the chunk shader together with the other translucent faces
+        if self.debug_view == DebugView::None {
+            self.water_shader.use_program();
+            self.water_shader.set_int("textureArray", 0);
+            self.water_shader.set_mat4("view_projection", &view_projection);
+            self.water_shader.set_float("animationTime", self.start.elapsed().as_secs_f32());
+            self.set_lighting_uniforms(&self.water_shader, camera, time, fog_end);
+            self.draw();
+            self.shader.use_program();
+        }
+
+        for (_, chunk) in &translucent_chunks {
+            self.draw_commands.extend(chunk.translucent_draw_commands.iter().cloned());
+        }
+        self.shader.set_bool("translucentPass", true);
+        self.draw();
+
+        gl::DepthMask(TRUE);
+        gl::Disable(BLEND);
+    }
+
+    /// Uniforms `include/lighting.glsl` needs, shared by the forward shader and the deferred resolve pass.
+    /// The shader has to be in use already
+    unsafe fn set_lighting_uniforms(&self, shader: &Shader, camera: &Camera, time: &WorldTime, fog_end: f32) {
+        shader.set_float("skyLightMultiplier", time.skylight_multiplier());
+
+        shader.set_int("shadowMaps", 1);
+        shader.set_float("shadowStrength", if self.shadow_map.enabled { time.shadow_strength() } else { 0.0 });
+        shader.set_vec3("lightDirection", &time.light_direction());
+        for (index, cascade) in self.shadow_map.cascades.iter().enumerate() {
+            shader.set_mat4(&format!("lightMatrices[{}]", index), &cascade.view_projection);
+            shader.set_float(&format!("cascadeEnds[{}]", index), cascade.end);
+            shader.set_float(&format!("cascadeTexelSizes[{}]", index), cascade.texel_size);
+        }
+
+        if self.underwater {
+            shader.set_int("fogMode", FogMode::Exponential as i32);
+            shader.set_float("fogEnd", UNDERWATER_FOG_END);
+            shader.set_float("fogDensity", UNDERWATER_FOG_DENSITY);
+            shader.set_vec3("fogColor", &(UNDERWATER_FOG_COLOR * time.skylight_multiplier()));
+        } else {
+            shader.set_int("fogMode", self.fog.mode as i32);
+            shader.set_float("fogStart", fog_end * self.fog.start);
+            shader.set_float("fogEnd", fog_end);
+            shader.set_float("fogDensity", self.fog.density);
+            shader.set_vec3("fogColor", &time.horizon_color());
+        }
+        shader.set_vec3("cameraPosition", &camera.position);
+        shader.set_vec3("cameraFront", &camera.front);
+    }
+
+    /// Draws the opaque faces of every chunk inside each cascade into the shadow map,
+    /// every face is drawn since the camera's backface culling doesn't apply to the light
+    unsafe fn render_shadows(&mut self, chunks: &HashMap<ChunkPosition, Chunk>, camera: &Camera, light_direction: Vec3, max_distance: f32) {
+        self.shadow_map.update(camera, light_direction, max_distance);
+
+        let mut viewport = [0; 4];
+        gl::GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        let mut framebuffer = 0;
+        gl::GetIntegerv(DRAW_FRAMEBUFFER_BINDING, &mut framebuffer);
+
+        self.shadow_shader.use_program();
+        self.shadow_shader.set_int("textureArray", 0);
+        self.shadow_shader.set_vec3("cameraPosition", &camera.position);
+
+        // pushes the depth away from the light a bit more on slopes to prevent shadow acne
+        gl::Enable(POLYGON_OFFSET_FILL);
+        gl::PolygonOffset(2.0, 4.0);
+
+        let cascades = self.shadow_map.cascades;
+        for (index, cascade) in cascades.iter().enumerate() {
+            self.shadow_map.bind_cascade(index);
+            self.shadow_shader.set_mat4("view_projection", &cascade.view_projection);
+
+            let frustum = Frustum::create(cascade.view_projection);
+            for (pos, chunk) in chunks {
+                let world_pos = pos.world_pos();
+                if frustum.test_aabb(world_pos, world_pos + Vec3::broadcast(CS_F32)) {
+                    self.draw_commands.extend(chunk.draw_commands.iter().cloned());
                 }
-                index += 1;
             }
+            self.draw();
         }
 
+        gl::Disable(POLYGON_OFFSET_FILL);
+        gl::BindFramebuffer(FRAMEBUFFER, framebuffer as u32);
+        gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+    }
+
+    pub fn increase_render_distance(&mut self) {
+        self.render_distance = (self.render_distance + 2).min(MAX_RENDER_DISTANCE);
+    }
+
+    pub fn decrease_render_distance(&mut self) {
+        self.render_distance = (self.render_distance - 2).max(2);
+    }
+
+    pub fn cycle_fog(&mut self) {
+        self.fog.mode = self.fog.mode.next();
+    }
+
+    /// Draws every queued command in one multi-draw call and clears the queue
+    unsafe fn draw(&mut self) {
         let command_amount = self.draw_commands.len();
 
         if command_amount == 0 {
@@ -161,7 +407,6 @@ impl ChunkRenderer {
         let data = self.draw_commands.as_ptr() as *const c_void;
         gl::BufferData(DRAW_INDIRECT_BUFFER, size, data, DYNAMIC_DRAW);
 
-        gl::BindVertexArray(self.vao);
         gl::BindBuffer(ELEMENT_ARRAY_BUFFER, self.ibo);
         gl::BindBufferBase(SHADER_STORAGE_BUFFER, 0, self.ssbo);
 
@@ -181,23 +426,62 @@ impl ChunkRenderer {
         self.draw_commands.clear()
     }
 
-    pub unsafe fn get_draw_command(&mut self, quad_count: u32, base_instance: u32) -> DrawElementsIndirectCommand {
-        let requested_size = quad_count * QUAD_SIZE_BYTES;
+    /// Meshes the chunk and uploads every non-empty face direction as its own draw command,
+    /// replacing the previous mesh if there was one
+    pub unsafe fn upload_chunk(&mut self, pos: &ChunkPosition, world: &mut World) {
+        let mesh = greedy_mesh(world, *pos);
+
+        let chunk = world.chunks.get_mut(pos).unwrap();
+        self.free_chunk(chunk);
+        let mut complete = true;
 
-        // todo: best fit algorithm if buffer is full
-        if (BUFFER_SIZE - self.allocation_end) < requested_size {
-            panic!();
+        for (face, vertices) in mesh.opaque.into_iter().enumerate().filter(|(_, vertices)| !vertices.is_empty()) {
+            match self.upload_face(pos, face, vertices) {
+                Some(command) => { chunk.add_draw_command(command) }
+                None => { complete = false }
+            }
         }
+        for (face, vertices) in mesh.translucent.into_iter().enumerate().filter(|(_, vertices)| !vertices.is_empty()) {
+            match self.upload_face(pos, face, vertices) {
+                Some(command) => { chunk.add_translucent_draw_command(command) }
+                None => { complete = false }
+            }
+        }
+        for (face, vertices) in mesh.fluid.into_iter().enumerate().filter(|(_, vertices)| !vertices.is_empty()) {
+            match self.upload_face(pos, face, vertices) {
+                Some(command) => { chunk.add_fluid_draw_command(command) }
+                None => { complete = false }
+            }
+        }
+        // whatever didn't fit into the buffer is tried again once unloaded chunks made room
+        chunk.dirty = !complete;
+    }
 
-        let slot = BufferSlot {
-            start_bytes: self.allocation_end,
-            size_byes: requested_size,
-        };
-        let cmd = create_command(&slot, base_instance);
-        self.used_slots.push(slot);
-        self.allocation_end += requested_size;
+    /// `None` if the buffer is full
+    unsafe fn upload_face(&mut self, pos: &ChunkPosition, face: usize, vertices: Vec<u64>) -> Option<DrawElementsIndirectCommand> {
+        // NOTE: Might be issue with negative numbers
+        let base_instance = ((pos.x & 0x7FF) << 21) | ((pos.y & 0x7F) << 14) | ((pos.z & 0x7FF) << 3) | face as i32;
+        let command = self.get_draw_command((vertices.len() / QUAD_WORDS) as u32, base_instance as u32)?;
+        self.upload_mesh(&command, vertices);
+        Some(command)
+    }
+
+    /// Gives the buffer space of every draw command of the chunk back to the allocator
+    pub fn free_chunk(&mut self, chunk: &mut Chunk) {
+        for command in chunk.draw_commands.drain(..)
+            .chain(chunk.translucent_draw_commands.drain(..))
+            .chain(chunk.fluid_draw_commands.drain(..)) {
+            self.allocator.free(BufferSlot {
+                start_bytes: (command.base_quad >> 2) * QUAD_SIZE_BYTES,
+                size_byes: (command.index_count / 6) * QUAD_SIZE_BYTES,
+            });
+        }
+    }
 
-        cmd
+    /// `None` if there is no room left in the buffer
+    pub unsafe fn get_draw_command(&mut self, quad_count: u32, base_instance: u32) -> Option<DrawElementsIndirectCommand> {
+        let slot = self.allocator.allocate(quad_count * QUAD_SIZE_BYTES)?;
+        Some(create_command(&slot, base_instance))
     }
 
     pub unsafe fn upload_mesh(&self, command: &DrawElementsIndirectCommand, vertices: Vec<u64>) {
@@ -213,6 +497,31 @@ impl ChunkRenderer {
     }
 }
 
+/// Furthest render distance at which every loaded column fits into `CHUNK_MEMORY_BUDGET`, even if none of its
+/// chunks are left out for being empty. The streamer loads one column more than the render distance and keeps
+/// one more than that before unloading
+const fn max_render_distance() -> i32 {
+    let chunk_bytes = CS * CS * CS * (size_of::<Block>() + size_of::<u16>());
+    let column_bytes = chunk_bytes * (MAX_CHUNK_Y - MIN_CHUNK_Y + 1) as usize;
+    let columns = CHUNK_MEMORY_BUDGET / column_bytes;
+    // columns in a circle are about pi times its radius squared
+    (columns * 7 / 22).isqrt() as i32 - 2
+}
+
+/// cpu backface culling, the face direction is stored in the lowest 3 bits of the base instance
+fn is_face_visible(command: &DrawElementsIndirectCommand, pos: &ChunkPosition, camera: &Camera) -> bool {
+    let camera_chunk = (camera.position / CS_F32).map(|it| it.floor());
+    match command.base_instance & 0x7 {
+        0 => { camera_chunk.y >= pos.y as f32 }
+        1 => { camera_chunk.y <= pos.y as f32 }
+        2 => { camera_chunk.x >= pos.x as f32 }
+        3 => { camera_chunk.x <= pos.x as f32 }
+        4 => { camera_chunk.z <= pos.z as f32 }
+        5 => { camera_chunk.z >= pos.z as f32 }
+        _ => { false }
+    }
+}
+
 fn create_command(slot: &BufferSlot, base_instance: u32) -> DrawElementsIndirectCommand {
     DrawElementsIndirectCommand {
         index_count: (slot.size_byes / QUAD_SIZE_BYTES) * 6,
@@ -222,3 +531,34 @@ fn create_command(slot: &BufferSlot, base_instance: u32) -> DrawElementsIndirect
         base_instance,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::render::chunk_renderer::{BufferAllocator, BufferSlot};
+    use crate::world::tick;
+
+    #[test]
+    fn freed_slots_are_merged() {
+        let size = 10_000;
+        let mut allocator = BufferAllocator::new(size);
+        let mut slots: Vec<BufferSlot> = Vec::new();
+
+        // chunks coming and going like while the camera moves, never more than half the buffer in use
+        for step in 0..100_000 {
+            let random = tick::hash(&[step]);
+            if slots.len() > 40 || (!slots.is_empty() && random.is_multiple_of(2)) {
+                let slot = slots.swap_remove((random >> 8) as usize % slots.len());
+                allocator.free(slot);
+            } else {
+                let requested = 1 + (random >> 16) as u32 % 120;
+                slots.push(allocator.allocate(requested).unwrap_or_else(|| panic!("out of space at step {}", step)));
+            }
+        }
+
+        for slot in slots {
+            allocator.free(slot);
+        }
+        assert!(allocator.free_slots.is_empty());
+        assert_eq!(allocator.allocation_end, 0);
+    }
+}
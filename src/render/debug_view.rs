@@ -0,0 +1,42 @@
+/// Debug visualisations of the chunk shader, the index is passed to the `debugView` uniform
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DebugView {
+    None,
+    FaceColors,
+    ChunkColors,
+    QuadOutlines,
+    AmbientOcclusion,
+    LightLevel,
+    LevelOfDetail,
+    Normals,
+}
+
+impl DebugView {
+    const ALL: [DebugView; 8] = [
+        DebugView::None,
+        DebugView::FaceColors,
+        DebugView::ChunkColors,
+        DebugView::QuadOutlines,
+        DebugView::AmbientOcclusion,
+        DebugView::LightLevel,
+        DebugView::LevelOfDetail,
+        DebugView::Normals,
+    ];
+
+    pub fn next(self) -> DebugView {
+        DebugView::ALL[(self as usize + 1) % DebugView::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugView::None => { "none" }
+            DebugView::FaceColors => { "face colors" }
+            DebugView::ChunkColors => { "chunk colors" }
+            DebugView::QuadOutlines => { "quad outlines" }
+            DebugView::AmbientOcclusion => { "ambient occlusion" }
+            DebugView::LightLevel => { "light level" }
+            DebugView::LevelOfDetail => { "level of detail" }
+            DebugView::Normals => { "normals" }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use gl::{BLEND, COLOR_BUFFER_BIT, DEPTH_BUFFER_BIT, DEPTH_TEST, DRAW_FRAMEBUFFER, DRAW_FRAMEBUFFER_BINDING, FILL, FRAMEBUFFER, FRONT_AND_BACK, NEAREST, POLYGON_MODE, READ_FRAMEBUFFER, RGBA16F, RGBA8, TEXTURE2, TEXTURE3, TEXTURE4, TEXTURE5, TEXTURE_2D, TRIANGLES, VIEWPORT};
+use gl::types::GLenum;
+use ultraviolet::Vec3;
+use crate::render::camera::Camera;
+use crate::render::render_target::RenderTarget;
+use crate::render::shaders::Shader;
+
+/// matches MAX_POINT_LIGHTS in deferred.frag
+pub const MAX_POINT_LIGHTS: usize = 32;
+
+/// Whether opaque chunks are lit while they are drawn, or drawn into a G-buffer and lit in a fullscreen pass
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shading {
+    Forward,
+    Deferred,
+}
+
+impl Shading {
+    pub fn next(self) -> Shading {
+        match self {
+            Shading::Forward => { Shading::Deferred }
+            Shading::Deferred => { Shading::Forward }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shading::Forward => { "forward" }
+            Shading::Deferred => { "deferred" }
+        }
+    }
+}
+
+/// Which ambient occlusion the deferred resolve pass uses, the index is passed to the `aoMode` uniform
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AmbientOcclusion {
+    /// per vertex ao from the mesher
+    Baked,
+    /// ssao from the G-buffer depth and normals
+    Screen,
+    Both,
+}
+
+impl AmbientOcclusion {
+    const ALL: [AmbientOcclusion; 3] = [
+        AmbientOcclusion::Baked,
+        AmbientOcclusion::Screen,
+        AmbientOcclusion::Both,
+    ];
+
+    pub fn next(self) -> AmbientOcclusion {
+        AmbientOcclusion::ALL[(self as usize + 1) % AmbientOcclusion::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AmbientOcclusion::Baked => { "baked" }
+            AmbientOcclusion::Screen => { "ssao" }
+            AmbientOcclusion::Both => { "baked + ssao" }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    /// distance in blocks where the light has faded out completely
+    pub radius: f32,
+}
+
+/// G-buffer of the deferred path: albedo, normal with baked ao, and the baked light levels plus depth.
+/// The resolve pass lights it into whatever framebuffer was bound before the geometry got drawn
+pub struct DeferredRenderer {
+    gbuffer: RenderTarget,
+    resolve_shader: Shader,
+    vao: u32,
+    /// framebuffer the resolved image goes to
+    target: u32,
+    pub ambient_occlusion: AmbientOcclusion,
+    /// dynamic lights, only the deferred path draws them
+    pub point_lights: Vec<PointLight>,
+}
+
+impl DeferredRenderer {
+    pub unsafe fn create(resolve_shader: Shader) -> DeferredRenderer {
+        let mut renderer = DeferredRenderer {
+            // resized to the viewport the first time it is used
+            gbuffer: RenderTarget::create(1, 1, &[RGBA16F, RGBA8, RGBA8], true),
+            resolve_shader,
+            vao: 0,
+            target: 0,
+            ambient_occlusion: AmbientOcclusion::Baked,
+            point_lights: Vec::new(),
+        };
+        gl::GenVertexArrays(1, &mut renderer.vao);
+        renderer
+    }
+
+    pub fn resolve_shader(&self) -> &Shader {
+        &self.resolve_shader
+    }
+
+    /// Adds a light, replacing the oldest one once there are too many
+    pub fn add_point_light(&mut self, light: PointLight) {
+        if self.point_lights.len() == MAX_POINT_LIGHTS {
+            self.point_lights.remove(0);
+        }
+        self.point_lights.push(light);
+    }
+
+    /// Binds and clears the G-buffer, sized to the current viewport
+    pub unsafe fn begin_geometry(&mut self) {
+        let mut viewport = [0; 4];
+        gl::GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        let mut target = 0;
+        gl::GetIntegerv(DRAW_FRAMEBUFFER_BINDING, &mut target);
+        self.target = target as u32;
+
+        self.gbuffer.resize(viewport[2], viewport[3]);
+        self.gbuffer.bind();
+        gl::Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
+    }
+
+    /// Lights the G-buffer into the framebuffer that was bound before `begin_geometry`, then copies the depth
+    /// over so translucent chunks drawn afterwards are still hidden behind opaque ones.
+    /// The lighting uniforms of the resolve shader have to be set before this
+    pub unsafe fn resolve(&self, camera: &Camera) {
+        gl::BindFramebuffer(FRAMEBUFFER, self.target);
+
+        let shader = &self.resolve_shader;
+        shader.use_program();
+        bind_texture(TEXTURE2, self.gbuffer.color[0]);
+        bind_texture(TEXTURE3, self.gbuffer.color[1]);
+        bind_texture(TEXTURE4, self.gbuffer.color[2]);
+        bind_texture(TEXTURE5, self.gbuffer.depth.unwrap());
+        shader.set_int("albedoTexture", 2);
+        shader.set_int("normalTexture", 3);
+        shader.set_int("lightTexture", 4);
+        shader.set_int("depthTexture", 5);
+
+        let projection = camera.projection_matrix();
+        let view = camera.view_matrix();
+        shader.set_mat4("projection", &projection);
+        shader.set_mat4("inverse_projection", &projection.inversed());
+        shader.set_mat4("view", &view);
+        shader.set_mat4("inverse_view_projection", &(projection * view).inversed());
+        shader.set_int("aoMode", self.ambient_occlusion as i32);
+
+        shader.set_int("pointLightCount", self.point_lights.len() as i32);
+        for (index, light) in self.point_lights.iter().enumerate() {
+            shader.set_vec3(&format!("pointLightPositions[{}]", index), &light.position);
+            shader.set_vec3(&format!("pointLightColors[{}]", index), &light.color);
+            shader.set_float(&format!("pointLightRadii[{}]", index), light.radius);
+        }
+
+        let mut polygon_mode = [0; 2];
+        gl::GetIntegerv(POLYGON_MODE, polygon_mode.as_mut_ptr());
+        gl::PolygonMode(FRONT_AND_BACK, FILL);
+        gl::Disable(DEPTH_TEST);
+        gl::Disable(BLEND);
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(TRIANGLES, 0, 3);
+        gl::BindVertexArray(0);
+        gl::Enable(DEPTH_TEST);
+        gl::PolygonMode(FRONT_AND_BACK, polygon_mode[0] as GLenum);
+
+        gl::BindFramebuffer(READ_FRAMEBUFFER, self.gbuffer.framebuffer);
+        gl::BindFramebuffer(DRAW_FRAMEBUFFER, self.target);
+        let (width, height) = (self.gbuffer.width, self.gbuffer.height);
+        gl::BlitFramebuffer(0, 0, width, height, 0, 0, width, height, DEPTH_BUFFER_BIT, NEAREST);
+        gl::BindFramebuffer(FRAMEBUFFER, self.target);
+    }
+}
+
+unsafe fn bind_texture(unit: GLenum, texture: u32) {
+    gl::ActiveTexture(unit);
+    gl::BindTexture(TEXTURE_2D, texture);
+}
@@ -0,0 +1,63 @@
+use ultraviolet::Vec3;
+use crate::world::chunk::chunk::CS_F32;
+
+/// Fog while the camera is inside water, it replaces the distance fog and always uses the exponential mode
+pub const UNDERWATER_FOG_END: f32 = 32.0;
+pub const UNDERWATER_FOG_DENSITY: f32 = 1.6;
+/// scaled by the sky light brightness, so the water gets dark at night
+pub const UNDERWATER_FOG_COLOR: Vec3 = Vec3::new(0.05, 0.22, 0.35);
+/// the whole screen gets multiplied with this while under water
+pub const UNDERWATER_TINT: Vec3 = Vec3::new(0.55, 0.8, 1.0);
+
+/// How fog thickens with distance, the index is passed to the `fogMode` uniform
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FogMode {
+    None,
+    Linear,
+    Exponential,
+}
+
+impl FogMode {
+    const ALL: [FogMode; 3] = [
+        FogMode::None,
+        FogMode::Linear,
+        FogMode::Exponential,
+    ];
+
+    pub fn next(self) -> FogMode {
+        FogMode::ALL[(self as usize + 1) % FogMode::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FogMode::None => { "none" }
+            FogMode::Linear => { "linear" }
+            FogMode::Exponential => { "exponential" }
+        }
+    }
+}
+
+/// Distance fog, it ends at the render distance so chunks fade in at the edge instead of popping in.
+/// Its colour is the horizon colour of the sky
+pub struct Fog {
+    pub mode: FogMode,
+    /// where linear fog starts, as a fraction of the render distance
+    pub start: f32,
+    /// how quickly exponential fog thickens, relative to the render distance
+    pub density: f32,
+}
+
+impl Fog {
+    pub fn new() -> Fog {
+        Fog {
+            mode: FogMode::Linear,
+            start: 0.6,
+            density: 2.5,
+        }
+    }
+
+    /// Distance in blocks where fog fully covers everything
+    pub fn end(&self, render_distance: i32) -> f32 {
+        render_distance as f32 * CS_F32
+    }
+}
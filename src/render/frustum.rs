@@ -21,23 +21,24 @@ impl Frustum {
     pub fn set(&mut self, view_projection: Mat4) {
 
         #[inline]
-        fn create_plane(mat: Mat4, index: usize) -> Vec4 {
+        fn create_plane(mat: Mat4, index: usize, sign: f32) -> Vec4 {
             let mut plane = Vec4::new(
-                mat.cols[0].w + mat.cols[0].index(index),
-                mat.cols[1].w + mat.cols[1].index(index),
-                mat.cols[2].w + mat.cols[2].index(index),
-                mat.cols[3].w + mat.cols[3].index(index),
+                mat.cols[0].w + sign * mat.cols[0].index(index),
+                mat.cols[1].w + sign * mat.cols[1].index(index),
+                mat.cols[2].w + sign * mat.cols[2].index(index),
+                mat.cols[3].w + sign * mat.cols[3].index(index),
             );
             plane *= 1.0 / plane.mag();
             plane
         }
 
-        self.planes[0] = create_plane(view_projection, 0);
-        self.planes[1] = create_plane(view_projection, 0);
-        self.planes[2] = create_plane(view_projection, 1);
-        self.planes[3] = create_plane(view_projection, 1);
-        self.planes[4] = create_plane(view_projection, 2);
-        self.planes[5] = create_plane(view_projection, 2);
+        // left and right, bottom and top, near and far
+        self.planes[0] = create_plane(view_projection, 0, 1.0);
+        self.planes[1] = create_plane(view_projection, 0, -1.0);
+        self.planes[2] = create_plane(view_projection, 1, 1.0);
+        self.planes[3] = create_plane(view_projection, 1, -1.0);
+        self.planes[4] = create_plane(view_projection, 2, 1.0);
+        self.planes[5] = create_plane(view_projection, 2, -1.0);
     }
 
     pub fn test_aabb(&self, min: Vec3, max: Vec3) -> bool {
@@ -2,4 +2,11 @@ pub mod shaders;
 pub mod camera;
 pub mod textures;
 pub mod chunk_renderer;
-pub mod frustum;
\ No newline at end of file
+pub mod frustum;
+pub mod debug_view;
+pub mod sky_renderer;
+pub mod fog;
+pub mod shadow_map;
+pub mod render_target;
+pub mod post_processing;
+pub mod deferred;
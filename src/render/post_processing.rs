@@ -0,0 +1,137 @@
+use gl::{COLOR_BUFFER_BIT, DEPTH_BUFFER_BIT, DEPTH_TEST, DRAW_FRAMEBUFFER, FILL, FRAMEBUFFER, FRONT_AND_BACK, NEAREST, POLYGON_MODE, READ_FRAMEBUFFER, RGBA16F, TEXTURE0, TEXTURE1, TEXTURE_2D, TRIANGLES};
+use gl::types::GLenum;
+use ultraviolet::Vec3;
+use crate::render::camera::Camera;
+use crate::render::render_target::RenderTarget;
+use crate::render::shaders::Shader;
+
+const FULLSCREEN_VERTEX_SHADER: &str = "resources/post/fullscreen.vert";
+
+/// A fullscreen shader of the post processing chain
+pub struct PostPass {
+    pub name: &'static str,
+    pub enabled: bool,
+    shader: Shader,
+}
+
+/// The world gets rendered into an HDR target, then every enabled pass runs in order,
+/// each one reading the output of the one before. The last pass writes to the window
+pub struct PostProcessing {
+    pub passes: Vec<PostPass>,
+    pub exposure: f32,
+    /// colour the tonemap pass multiplies the scene with
+    pub tint: Vec3,
+    /// colour and depth of the rendered world
+    scene: RenderTarget,
+    /// passes alternate between writing into these two
+    ping_pong: [RenderTarget; 2],
+    vao: u32,
+}
+
+impl PostProcessing {
+    pub unsafe fn create(width: i32, height: i32) -> PostProcessing {
+        let mut post_processing = PostProcessing {
+            passes: Vec::new(),
+            exposure: 1.0,
+            tint: Vec3::one(),
+            scene: RenderTarget::create(width, height, &[RGBA16F], true),
+            ping_pong: [
+                RenderTarget::create(width, height, &[RGBA16F], false),
+                RenderTarget::create(width, height, &[RGBA16F], false),
+            ],
+            vao: 0,
+        };
+        // the fullscreen triangle is generated from gl_VertexID, but core profile still needs a vao bound to draw
+        gl::GenVertexArrays(1, &mut post_processing.vao);
+        post_processing
+    }
+
+    /// Adds a pass to the end of the chain, `fragment_path` gets drawn with the shared fullscreen vertex shader
+    pub fn add_pass(&mut self, name: &'static str, fragment_path: &str, enabled: bool) {
+        self.passes.push(PostPass {
+            name,
+            enabled,
+            shader: Shader::new(FULLSCREEN_VERTEX_SHADER, fragment_path),
+        });
+    }
+
+    /// Turns the pass at `index` on or off, does nothing if there is no such pass
+    pub fn toggle_pass(&mut self, index: usize) {
+        if let Some(pass) = self.passes.get_mut(index) {
+            pass.enabled = !pass.enabled;
+        }
+    }
+
+    pub unsafe fn resize(&mut self, width: i32, height: i32) {
+        self.scene.resize(width, height);
+        for target in &mut self.ping_pong {
+            target.resize(width, height);
+        }
+    }
+
+    /// Depth texture of the rendered world, valid until the next `begin`
+    pub fn scene_depth(&self) -> u32 {
+        self.scene.depth.unwrap()
+    }
+
+    /// Everything drawn after this ends up in the HDR target instead of the window
+    pub unsafe fn begin(&self) {
+        self.scene.bind();
+        gl::Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
+    }
+
+    /// Runs every enabled pass and writes the result to the window
+    pub unsafe fn finish(&self, camera: &Camera) {
+        let mut polygon_mode = [0; 2];
+        gl::GetIntegerv(POLYGON_MODE, polygon_mode.as_mut_ptr());
+        gl::PolygonMode(FRONT_AND_BACK, FILL);
+        gl::Disable(DEPTH_TEST);
+        gl::BindVertexArray(self.vao);
+
+        let projection = camera.projection_matrix();
+        let enabled: Vec<&PostPass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+        let mut input = self.scene.color[0];
+
+        for (index, pass) in enabled.iter().enumerate() {
+            let output = &self.ping_pong[index % 2];
+            if index == enabled.len() - 1 {
+                gl::BindFramebuffer(FRAMEBUFFER, 0);
+                gl::Viewport(0, 0, self.scene.width, self.scene.height);
+            } else {
+                output.bind();
+            }
+
+            pass.shader.use_program();
+            bind_texture(TEXTURE0, input);
+            bind_texture(TEXTURE1, self.scene_depth());
+            pass.shader.set_int("inputTexture", 0);
+            pass.shader.set_int("depthTexture", 1);
+            pass.shader.set_vec2("texelSize", 1.0 / self.scene.width as f32, 1.0 / self.scene.height as f32);
+            pass.shader.set_float("exposure", self.exposure);
+            pass.shader.set_vec3("tint", &self.tint);
+            pass.shader.set_mat4("projection", &projection);
+            pass.shader.set_mat4("inverse_projection", &projection.inversed());
+
+            gl::DrawArrays(TRIANGLES, 0, 3);
+            input = output.color[0];
+        }
+
+        // nothing is enabled, the scene still has to end up on screen
+        if enabled.is_empty() {
+            gl::BindFramebuffer(READ_FRAMEBUFFER, self.scene.framebuffer);
+            gl::BindFramebuffer(DRAW_FRAMEBUFFER, 0);
+            let (width, height) = (self.scene.width, self.scene.height);
+            gl::BlitFramebuffer(0, 0, width, height, 0, 0, width, height, COLOR_BUFFER_BIT, NEAREST);
+        }
+
+        gl::BindFramebuffer(FRAMEBUFFER, 0);
+        gl::BindVertexArray(0);
+        gl::Enable(DEPTH_TEST);
+        gl::PolygonMode(FRONT_AND_BACK, polygon_mode[0] as GLenum);
+    }
+}
+
+unsafe fn bind_texture(unit: GLenum, texture: u32) {
+    gl::ActiveTexture(unit);
+    gl::BindTexture(TEXTURE_2D, texture);
+}
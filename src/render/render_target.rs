@@ -0,0 +1,89 @@
+use std::ptr;
+use gl::{CLAMP_TO_EDGE, COLOR_ATTACHMENT0, DEPTH_ATTACHMENT, DEPTH_COMPONENT, DEPTH_COMPONENT32F, FLOAT, FRAMEBUFFER, FRAMEBUFFER_COMPLETE, LINEAR, NEAREST, RGBA, TEXTURE_2D, TEXTURE_MAG_FILTER, TEXTURE_MIN_FILTER, TEXTURE_WRAP_S, TEXTURE_WRAP_T};
+use gl::types::{GLenum, GLint};
+
+/// Offscreen framebuffer with one texture per colour attachment and optionally a depth texture
+pub struct RenderTarget {
+    pub framebuffer: u32,
+    pub color: Vec<u32>,
+    pub depth: Option<u32>,
+    pub width: i32,
+    pub height: i32,
+    formats: Vec<GLenum>,
+}
+
+impl RenderTarget {
+    /// `formats` are the internal formats of the colour attachments, in attachment order
+    pub unsafe fn create(width: i32, height: i32, formats: &[GLenum], with_depth: bool) -> RenderTarget {
+        let mut target = RenderTarget {
+            framebuffer: 0,
+            color: Vec::new(),
+            depth: None,
+            width,
+            height,
+            formats: formats.to_vec(),
+        };
+
+        gl::GenFramebuffers(1, &mut target.framebuffer);
+        gl::BindFramebuffer(FRAMEBUFFER, target.framebuffer);
+
+        let mut attachments = Vec::new();
+        for (index, format) in formats.iter().enumerate() {
+            let texture = create_texture(width, height, *format, RGBA, LINEAR);
+            let attachment = COLOR_ATTACHMENT0 + index as GLenum;
+            gl::FramebufferTexture2D(FRAMEBUFFER, attachment, TEXTURE_2D, texture, 0);
+            target.color.push(texture);
+            attachments.push(attachment);
+        }
+        gl::DrawBuffers(attachments.len() as i32, attachments.as_ptr());
+
+        if with_depth {
+            let texture = create_texture(width, height, DEPTH_COMPONENT32F, DEPTH_COMPONENT, NEAREST);
+            gl::FramebufferTexture2D(FRAMEBUFFER, DEPTH_ATTACHMENT, TEXTURE_2D, texture, 0);
+            target.depth = Some(texture);
+        }
+
+        if gl::CheckFramebufferStatus(FRAMEBUFFER) != FRAMEBUFFER_COMPLETE {
+            panic!("render target {}x{} is incomplete", width, height);
+        }
+        gl::BindFramebuffer(FRAMEBUFFER, 0);
+
+        target
+    }
+
+    /// Recreates every texture at the new size, their contents are lost
+    pub unsafe fn resize(&mut self, width: i32, height: i32) {
+        if self.width == width && self.height == height {
+            return;
+        }
+        let resized = RenderTarget::create(width, height, &self.formats, self.depth.is_some());
+        self.delete();
+        *self = resized;
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::BindFramebuffer(FRAMEBUFFER, self.framebuffer);
+        gl::Viewport(0, 0, self.width, self.height);
+    }
+
+    unsafe fn delete(&mut self) {
+        gl::DeleteTextures(self.color.len() as i32, self.color.as_ptr());
+        if let Some(depth) = self.depth {
+            gl::DeleteTextures(1, &depth);
+        }
+        gl::DeleteFramebuffers(1, &self.framebuffer);
+    }
+}
+
+unsafe fn create_texture(width: i32, height: i32, internal_format: GLenum, format: GLenum, filter: GLenum) -> u32 {
+    let mut texture = 0;
+    gl::GenTextures(1, &mut texture);
+    gl::BindTexture(TEXTURE_2D, texture);
+    gl::TexImage2D(TEXTURE_2D, 0, internal_format as GLint, width, height, 0, format, FLOAT, ptr::null());
+    gl::TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, filter as GLint);
+    gl::TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, filter as GLint);
+    gl::TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+    gl::TexParameteri(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+    gl::BindTexture(TEXTURE_2D, 0);
+    texture
+}
@@ -1,6 +1,7 @@
 use std::ffi::{CString};
 use std::fs::File;
 use std::io::Read;
+use std::path::Path;
 use std::ptr;
 use gl::types::{GLchar, GLint};
 use ultraviolet::{Mat4, Vec3};
@@ -13,14 +14,8 @@ impl Shader {
     pub fn new(vertex_path: &str, fragment_path: &str) -> Shader {
         let mut shader = Shader { id: 0 };
 
-        let mut vert_shader_file = File::open(vertex_path).unwrap_or_else(|_| panic!("failed to open {}", vertex_path));
-        let mut frag_shader_file = File::open(fragment_path).unwrap_or_else(|_| panic!("failed to open {}", fragment_path));
-
-        let mut vertex_code = String::new();
-        let mut fragment_code = String::new();
-
-        vert_shader_file.read_to_string(&mut vertex_code).expect("Failed to read vertex shader");
-        frag_shader_file.read_to_string(&mut fragment_code).expect("Failed to read fragment shader");
+        let vertex_code = read_source(Path::new(vertex_path));
+        let fragment_code = read_source(Path::new(fragment_path));
 
         let v_shader_code = CString::new(vertex_code.as_bytes()).unwrap();
         let f_shader_code = CString::new(fragment_code.as_bytes()).unwrap();
@@ -59,11 +54,25 @@ impl Shader {
         gl::Uniform1i(gl::GetUniformLocation(self.id, cstr.as_ptr()), value);
     }
 
+    pub unsafe fn set_float(&self, name: &str, value: f32) {
+        let cstr = CString::new(name).unwrap();
+        gl::Uniform1f(gl::GetUniformLocation(self.id, cstr.as_ptr()), value);
+    }
+
+    pub unsafe fn set_bool(&self, name: &str, value: bool) {
+        self.set_int(name, value as i32);
+    }
+
     pub unsafe fn set_mat4(&self, name: &str, mat: &Mat4) {
         let cstr = CString::new(name).unwrap();
         gl::UniformMatrix4fv(gl::GetUniformLocation(self.id, cstr.as_ptr()), 1, gl::FALSE, mat.as_ptr());
     }
 
+    pub unsafe fn set_vec2(&self, name: &str, x: f32, y: f32) {
+        let cstr = CString::new(name).unwrap();
+        gl::Uniform2f(gl::GetUniformLocation(self.id, cstr.as_ptr()), x, y);
+    }
+
     pub unsafe fn set_vec3(&self, name: &str, vec: &Vec3) {
         let cstr = CString::new(name).unwrap();
         gl::Uniform3f(gl::GetUniformLocation(self.id, cstr.as_ptr()), vec.x, vec.y, vec.z);
@@ -98,4 +107,21 @@ impl Shader {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Reads a shader file, replacing every `#include "path"` line with the contents of that file.
+/// Include paths are relative to the file that includes them
+fn read_source(path: &Path) -> String {
+    let mut file = File::open(path).unwrap_or_else(|_| panic!("failed to open {}", path.display()));
+    let mut code = String::new();
+    file.read_to_string(&mut code).unwrap_or_else(|_| panic!("failed to read {}", path.display()));
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    code.lines()
+        .map(|line| match line.trim().strip_prefix("#include") {
+            Some(include) => { read_source(&directory.join(include.trim().trim_matches('"'))) }
+            None => { line.to_string() }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,146 @@
+use std::ptr;
+use gl::{CLAMP_TO_BORDER, COMPARE_REF_TO_TEXTURE, DEPTH_ATTACHMENT, DEPTH_BUFFER_BIT, DEPTH_COMPONENT, DEPTH_COMPONENT32F, FLOAT, FRAMEBUFFER, LEQUAL, LINEAR, NONE, TEXTURE_2D_ARRAY, TEXTURE_BORDER_COLOR, TEXTURE_COMPARE_FUNC, TEXTURE_COMPARE_MODE, TEXTURE_MAG_FILTER, TEXTURE_MIN_FILTER, TEXTURE_WRAP_S, TEXTURE_WRAP_T};
+use gl::types::{GLenum, GLint};
+use ultraviolet::projection::orthographic_gl;
+use ultraviolet::{Mat4, Vec3, Vec4};
+use crate::render::camera::{Camera, FOV, NEAR_PLANE};
+
+pub const CASCADES: usize = 3;
+const SHADOW_MAP_SIZE: i32 = 2048;
+/// shadows are never drawn further away than this, even with a larger render distance
+const MAX_SHADOW_DISTANCE: f32 = 256.0;
+/// how much the cascade splits lean towards logarithmic (1.0) instead of even (0.0) spacing
+const SPLIT_LAMBDA: f32 = 0.8;
+/// extra depth towards the light, so blocks outside the view still cast shadows into it
+const CASTER_MARGIN: f32 = 128.0;
+
+#[derive(Clone, Copy)]
+pub struct Cascade {
+    pub view_projection: Mat4,
+    /// view depth where this cascade stops and the next one takes over
+    pub end: f32,
+    /// size of one shadow map texel in blocks
+    pub texel_size: f32,
+}
+
+/// Cascaded shadow maps of the sun (or moon), one depth texture array layer per cascade
+pub struct ShadowMap {
+    framebuffer: u32,
+    depth_texture: u32,
+    pub cascades: [Cascade; CASCADES],
+    pub enabled: bool,
+}
+
+impl ShadowMap {
+    pub unsafe fn create() -> ShadowMap {
+        let mut shadow_map = ShadowMap {
+            framebuffer: 0,
+            depth_texture: 0,
+            cascades: [Cascade { view_projection: Mat4::identity(), end: 0.0, texel_size: 0.0 }; CASCADES],
+            enabled: true,
+        };
+
+        gl::GenTextures(1, &mut shadow_map.depth_texture);
+        gl::BindTexture(TEXTURE_2D_ARRAY, shadow_map.depth_texture);
+        gl::TexImage3D(
+            TEXTURE_2D_ARRAY,
+            0,
+            DEPTH_COMPONENT32F as GLint,
+            SHADOW_MAP_SIZE,
+            SHADOW_MAP_SIZE,
+            CASCADES as i32,
+            0,
+            DEPTH_COMPONENT,
+            FLOAT,
+            ptr::null(),
+        );
+        // linear filtering with depth comparison gives a bilinear filtered result for every pcf tap
+        gl::TexParameteri(TEXTURE_2D_ARRAY, TEXTURE_MIN_FILTER, LINEAR as GLint);
+        gl::TexParameteri(TEXTURE_2D_ARRAY, TEXTURE_MAG_FILTER, LINEAR as GLint);
+        gl::TexParameteri(TEXTURE_2D_ARRAY, TEXTURE_COMPARE_MODE, COMPARE_REF_TO_TEXTURE as GLint);
+        gl::TexParameteri(TEXTURE_2D_ARRAY, TEXTURE_COMPARE_FUNC, LEQUAL as GLint);
+        // everything outside of the map is lit
+        gl::TexParameteri(TEXTURE_2D_ARRAY, TEXTURE_WRAP_S, CLAMP_TO_BORDER as GLint);
+        gl::TexParameteri(TEXTURE_2D_ARRAY, TEXTURE_WRAP_T, CLAMP_TO_BORDER as GLint);
+        let border = [1.0f32; 4];
+        gl::TexParameterfv(TEXTURE_2D_ARRAY, TEXTURE_BORDER_COLOR, border.as_ptr());
+        gl::BindTexture(TEXTURE_2D_ARRAY, 0);
+
+        gl::GenFramebuffers(1, &mut shadow_map.framebuffer);
+        gl::BindFramebuffer(FRAMEBUFFER, shadow_map.framebuffer);
+        gl::FramebufferTextureLayer(FRAMEBUFFER, DEPTH_ATTACHMENT, shadow_map.depth_texture, 0, 0);
+        gl::DrawBuffer(NONE);
+        gl::ReadBuffer(NONE);
+        gl::BindFramebuffer(FRAMEBUFFER, 0);
+
+        shadow_map
+    }
+
+    /// Fits every cascade around its slice of the camera frustum, `max_distance` is usually the render distance
+    pub fn update(&mut self, camera: &Camera, light_direction: Vec3, max_distance: f32) {
+        let far = max_distance.min(MAX_SHADOW_DISTANCE);
+        let mut start = NEAR_PLANE;
+
+        for (index, cascade) in self.cascades.iter_mut().enumerate() {
+            let fraction = (index + 1) as f32 / CASCADES as f32;
+            let logarithmic = NEAR_PLANE * (far / NEAR_PLANE).powf(fraction);
+            let even = NEAR_PLANE + (far - NEAR_PLANE) * fraction;
+            let end = SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * even;
+
+            *cascade = fit_cascade(camera, light_direction, start, end);
+            start = end;
+        }
+    }
+
+    /// Binds the layer of a cascade as the depth target and clears it
+    pub unsafe fn bind_cascade(&self, cascade: usize) {
+        gl::BindFramebuffer(FRAMEBUFFER, self.framebuffer);
+        gl::FramebufferTextureLayer(FRAMEBUFFER, DEPTH_ATTACHMENT, self.depth_texture, 0, cascade as i32);
+        gl::Viewport(0, 0, SHADOW_MAP_SIZE, SHADOW_MAP_SIZE);
+        gl::Clear(DEPTH_BUFFER_BIT);
+    }
+
+    pub unsafe fn bind_texture(&self, unit: GLenum) {
+        gl::ActiveTexture(unit);
+        gl::BindTexture(TEXTURE_2D_ARRAY, self.depth_texture);
+    }
+}
+
+/// Orthographic projection from the light around a bounding sphere of the frustum slice between `near` and `far`.
+/// The sphere doesn't change size when the camera turns, and the projection is snapped to whole texels,
+/// so shadow edges don't shimmer while moving
+fn fit_cascade(camera: &Camera, light_direction: Vec3, near: f32, far: f32) -> Cascade {
+    let tan_y = (FOV.to_radians() / 2.0).tan();
+    let tan_x = tan_y * camera.aspect_ratio;
+
+    let center = camera.position + camera.front * ((near + far) / 2.0);
+    let mut radius: f32 = 0.0;
+    for depth in [near, far] {
+        for (x, y) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+            let corner = camera.position
+                + camera.front * depth
+                + camera.right * (x * tan_x * depth)
+                + camera.up * (y * tan_y * depth);
+            radius = radius.max((corner - center).mag());
+        }
+    }
+    // rounded so float errors don't change the texel size every frame
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    // the sun never lines up with +z (it is tilted in `WorldTime::sun_direction`), so z is a safe up vector
+    let eye = center + light_direction * (radius + CASTER_MARGIN);
+    let view = Mat4::look_at(eye, center, Vec3::unit_z());
+    let mut projection = orthographic_gl(-radius, radius, -radius, radius, 0.0, radius * 2.0 + CASTER_MARGIN);
+
+    let half_size = SHADOW_MAP_SIZE as f32 / 2.0;
+    let origin = (projection * view) * Vec4::new(0.0, 0.0, 0.0, 1.0);
+    let (texel_x, texel_y) = (origin.x * half_size, origin.y * half_size);
+    projection.cols[3].x += (texel_x.round() - texel_x) / half_size;
+    projection.cols[3].y += (texel_y.round() - texel_y) / half_size;
+
+    Cascade {
+        view_projection: projection * view,
+        end: far,
+        texel_size: radius * 2.0 / SHADOW_MAP_SIZE as f32,
+    }
+}
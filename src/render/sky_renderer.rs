@@ -0,0 +1,50 @@
+use gl::{FALSE, LEQUAL, LESS, TRIANGLES, TRUE};
+use ultraviolet::Vec4;
+use crate::render::camera::Camera;
+use crate::render::shaders::Shader;
+use crate::world::time::WorldTime;
+
+/// Draws the sky gradient, sun and moon as a fullscreen triangle behind everything else
+pub struct SkyRenderer {
+    vao: u32,
+    shader: Shader,
+}
+
+impl SkyRenderer {
+    pub unsafe fn create(shader: Shader) -> SkyRenderer {
+        let mut renderer = SkyRenderer {
+            vao: 0,
+            shader,
+        };
+        // the triangle is generated from gl_VertexID, but core profile still needs a vao bound to draw
+        gl::GenVertexArrays(1, &mut renderer.vao);
+        renderer
+    }
+
+    pub unsafe fn render(&self, camera: &Camera, time: &WorldTime) {
+        self.shader.use_program();
+
+        // only the rotation of the camera matters, the sky is infinitely far away
+        let mut view = camera.view_matrix();
+        view.cols[3] = Vec4::unit_w();
+        let inverse_view_projection = (camera.projection_matrix() * view).inversed();
+        self.shader.set_mat4("inverse_view_projection", &inverse_view_projection);
+
+        self.shader.set_vec3("zenithColor", &time.zenith_color());
+        self.shader.set_vec3("horizonColor", &time.horizon_color());
+        self.shader.set_vec3("sunDirection", &time.sun_direction());
+        self.shader.set_vec3("moonDirection", &time.moon_direction());
+        self.shader.set_float("daylight", time.daylight());
+
+        // drawn at the far plane without writing depth, so chunks always end up in front of it
+        gl::DepthFunc(LEQUAL);
+        gl::DepthMask(FALSE);
+
+        gl::BindVertexArray(self.vao);
+        gl::DrawArrays(TRIANGLES, 0, 3);
+        gl::BindVertexArray(0);
+
+        gl::DepthMask(TRUE);
+        gl::DepthFunc(LESS);
+    }
+}
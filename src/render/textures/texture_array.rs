@@ -21,7 +21,8 @@ impl TextureArray {
             gl::TexImage3D(
                 gl::TEXTURE_2D_ARRAY,
                 0,
-                gl::RGBA8 as GLint,
+                // textures are stored in srgb, sampling converts them to linear for lighting
+                gl::SRGB8_ALPHA8 as GLint,
                 width as GLsizei,
                 height as GLsizei,
                 layers as GLsizei,
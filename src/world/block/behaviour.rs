@@ -0,0 +1,119 @@
+use crate::world::block::block::BlockType;
+use crate::world::block::registry::BlockRegistry;
+use crate::world::fluid;
+use crate::world::light::LightChannel;
+use crate::world::world::World;
+
+type BlockPos = [i32; 3];
+
+/// What a block type does on its own, every callback is optional. Each one gets the position of the block
+#[derive(Clone, Copy, Default)]
+pub struct BlockBehaviour {
+    /// a scheduled update of the block is due, see `World::schedule_update`
+    pub scheduled_tick: Option<fn(&mut World, BlockPos)>,
+    /// the block got picked by a random tick
+    pub random_tick: Option<fn(&mut World, BlockPos)>,
+    /// the block itself or one of its six neighbours changed
+    pub neighbour_changed: Option<fn(&mut World, BlockPos)>,
+}
+
+/// Ticks before a block without support starts falling, and between every block it falls
+const FALL_DELAY: u64 = 2;
+/// Sky light dirt needs for grass to spread onto it
+const GRASS_SPREAD_LIGHT: u8 = 9;
+
+const FLUID: BlockBehaviour = BlockBehaviour {
+    scheduled_tick: Some(fluid::update),
+    random_tick: None,
+    neighbour_changed: Some(fluid::schedule),
+};
+
+const FALLING: BlockBehaviour = BlockBehaviour {
+    scheduled_tick: Some(fall),
+    random_tick: None,
+    neighbour_changed: Some(schedule_fall),
+};
+
+const GRASS: BlockBehaviour = BlockBehaviour {
+    scheduled_tick: None,
+    random_tick: Some(grow_grass),
+    neighbour_changed: None,
+};
+
+/// Gives the built in blocks their behaviour, blocks missing from the registry are skipped
+pub fn register_defaults(registry: &mut BlockRegistry) {
+    for block_type in registry.block_types() {
+        if registry.is_fluid(block_type) {
+            registry.set_behaviour(block_type, FLUID);
+        }
+    }
+    if let Some(sand) = registry.find("sand") {
+        registry.set_behaviour(sand, FALLING);
+    }
+    if let (Some(grass), Some(_)) = (registry.find("grass"), registry.find("dirt")) {
+        registry.set_behaviour(grass, GRASS);
+    }
+}
+
+fn schedule_fall(world: &mut World, pos: BlockPos) {
+    world.schedule_update(pos, FALL_DELAY);
+}
+
+/// Moves the block one down if there is air or fluid below it, it schedules itself again from there
+fn fall(world: &mut World, [x, y, z]: BlockPos) {
+    let Some(below) = world.get_loaded_block(x, y - 1, z) else { return };
+    if below == BlockType::AIR || world.registry.is_fluid(below) {
+        let block_type = world.get_block(x, y, z);
+        world.set_block(x, y, z, BlockType::AIR);
+        world.set_block(x, y - 1, z, block_type);
+    }
+}
+
+/// Grass under an opaque block turns into dirt, otherwise it spreads to a random dirt block
+/// close by that has enough sky light above it
+fn grow_grass(world: &mut World, [x, y, z]: BlockPos) {
+    let grass = world.get_block(x, y, z);
+    let dirt = world.registry.get("dirt");
+    let covered = |world: &World, x: i32, y: i32, z: i32| world.registry.transparency(world.get_block(x, y + 1, z)).is_opaque();
+
+    if covered(world, x, y, z) {
+        world.set_block(x, y, z, dirt);
+        return;
+    }
+
+    // one block sideways, from three below up to one above
+    let random = world.random([x, y, z]);
+    let (tx, ty, tz) = (x + (random % 3) as i32 - 1, y + ((random >> 8) % 5) as i32 - 3, z + ((random >> 16) % 3) as i32 - 1);
+    if world.get_block(tx, ty, tz) == dirt
+        && !covered(world, tx, ty, tz)
+        && world.get_light(tx, ty + 1, tz, LightChannel::Sky) >= GRASS_SPREAD_LIGHT {
+        world.set_block(tx, ty, tz, grass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::block::{Block, BlockType};
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::{Chunk, ChunkPosition, CS};
+    use crate::world::settings::WorldSettings;
+    use crate::world::world::World;
+
+    #[test]
+    fn sand_falls_onto_the_ground() {
+        let mut world = World::new(BlockRegistry::load("resources/blocks.ron"), WorldSettings::default());
+        let dirt = world.registry.get("dirt");
+        let sand = world.registry.get("sand");
+        let mut chunk = Chunk::create(vec![Block::new(BlockType::AIR); CS * CS * CS]);
+        chunk.blocks[5 + 5 * CS * CS] = Block::new(dirt);
+        world.add_chunk(ChunkPosition { x: 0, y: 0, z: 0 }, chunk);
+
+        world.set_block(5, 10, 5, sand);
+        while world.pending_updates() > 0 {
+            world.tick();
+        }
+
+        assert_eq!(world.get_block(5, 10, 5), BlockType::AIR);
+        assert_eq!(world.get_block(5, 1, 5), sand);
+    }
+}
@@ -0,0 +1,40 @@
+use serde::Deserialize;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Block {
+    pub block_type: BlockType,
+    /// only used by fluids, 0 is a source and 1 to 7 is how far the fluid has flowed from one
+    pub level: u8,
+}
+
+impl Block {
+    pub fn new(block_type: BlockType) -> Block {
+        Block { block_type, level: 0 }
+    }
+}
+
+/// Numeric id of a block, what it actually is gets looked up in the `BlockRegistry`
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct BlockType(pub u16);
+
+impl BlockType {
+    /// Air is always registered with this id
+    pub const AIR: BlockType = BlockType(0);
+}
+
+/// How a block lets light and neighbouring faces through
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Deserialize)]
+pub enum Transparency {
+    /// fully solid, hides any face touching it
+    Opaque,
+    /// either fully solid or fully see-through per pixel (leaves), drawn in the opaque pass with alpha testing
+    Cutout,
+    /// partially see-through (glass, water), drawn in a separate blended pass
+    Translucent,
+}
+
+impl Transparency {
+    pub fn is_opaque(&self) -> bool {
+        *self == Transparency::Opaque
+    }
+}
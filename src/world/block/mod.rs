@@ -1 +1,3 @@
-pub mod block;
\ No newline at end of file
+pub mod block;
+pub mod registry;
+pub mod behaviour;
\ No newline at end of file
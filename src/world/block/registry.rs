@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use ron::extensions::Extensions;
+use ron::Options;
+use serde::Deserialize;
+use crate::world::block::behaviour::{self, BlockBehaviour};
+use crate::world::block::block::{BlockType, Transparency};
+
+const TEXTURE_DIRECTORY: &str = "resources/textures";
+
+/// A single entry of the block definitions file
+#[derive(Deserialize)]
+pub struct BlockDefinition {
+    pub name: String,
+    pub id: u16,
+    pub textures: TextureNames,
+    pub solid: bool,
+    pub transparency: Transparency,
+    /// red, green and blue light the block gives off, 0 to 15 each
+    #[serde(default)]
+    pub light_emission: [u8; 3],
+    /// meshed as a separate surface that sits a bit lower than the block and is drawn with the water shader
+    #[serde(default)]
+    pub fluid: bool,
+    pub hardness: f32,
+}
+
+/// Texture names per face, the more specific field wins (`top` > `all`, `right` > `side` > `all`)
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TextureNames {
+    pub all: Option<String>,
+    pub side: Option<String>,
+    pub top: Option<String>,
+    pub bottom: Option<String>,
+    pub right: Option<String>,
+    pub left: Option<String>,
+    pub front: Option<String>,
+    pub back: Option<String>,
+}
+
+impl TextureNames {
+    /// Texture names in face order (top, bottom, right, left, front, back)
+    fn resolve(&self, block_name: &str) -> [&str; 6] {
+        let all = self.all.as_deref();
+        let side = self.side.as_deref().or(all);
+        [
+            self.top.as_deref().or(all),
+            self.bottom.as_deref().or(all),
+            self.right.as_deref().or(side),
+            self.left.as_deref().or(side),
+            self.front.as_deref().or(side),
+            self.back.as_deref().or(side),
+        ].map(|name| name.unwrap_or_else(|| panic!("block {} is missing a texture", block_name)))
+    }
+}
+
+pub struct BlockProperties {
+    pub name: String,
+    pub solid: bool,
+    pub transparency: Transparency,
+    pub light_emission: [u8; 3],
+    pub fluid: bool,
+    pub hardness: f32,
+    /// texture array layer for each face (top, bottom, right, left, front, back)
+    pub textures: [u32; 6],
+    /// not part of the definitions file, built in behaviours get attached by block name
+    pub behaviour: BlockBehaviour,
+}
+
+pub struct BlockRegistry {
+    /// indexed by block id, ids without a definition are `None`
+    blocks: Vec<Option<BlockProperties>>,
+    names: HashMap<String, BlockType>,
+    /// texture name of each texture array layer
+    textures: Vec<String>,
+}
+
+impl BlockRegistry {
+    pub fn load(path: &str) -> BlockRegistry {
+        let mut file = File::open(path).unwrap_or_else(|_| panic!("failed to open {}", path));
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).expect("Failed to read block definitions");
+
+        // implicit_some lets textures be written as `(top: "grass_top")` instead of `(top: Some("grass_top"))`
+        let definitions: Vec<BlockDefinition> = Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(&contents)
+            .unwrap_or_else(|err| panic!("failed to parse {}: {}", path, err));
+        BlockRegistry::from_definitions(definitions)
+    }
+
+    pub fn from_definitions(definitions: Vec<BlockDefinition>) -> BlockRegistry {
+        let mut registry = BlockRegistry {
+            blocks: Vec::new(),
+            names: HashMap::new(),
+            textures: Vec::new(),
+        };
+
+        registry.insert(BlockType::AIR, BlockProperties {
+            name: String::from("air"),
+            solid: false,
+            // air never gets meshed, but it should never hide a face either
+            transparency: Transparency::Translucent,
+            light_emission: [0; 3],
+            fluid: false,
+            hardness: 0.0,
+            textures: [0; 6],
+            behaviour: BlockBehaviour::default(),
+        });
+
+        for definition in definitions {
+            if definition.id == BlockType::AIR.0 {
+                panic!("block {} uses id 0, which is reserved for air", definition.name);
+            }
+
+            let mut textures = [0; 6];
+            for (face, name) in definition.textures.resolve(&definition.name).iter().enumerate() {
+                textures[face] = registry.texture_layer(name);
+            }
+
+            registry.insert(BlockType(definition.id), BlockProperties {
+                name: definition.name,
+                solid: definition.solid,
+                transparency: definition.transparency,
+                light_emission: definition.light_emission,
+                fluid: definition.fluid,
+                hardness: definition.hardness,
+                textures,
+                behaviour: BlockBehaviour::default(),
+            });
+        }
+        behaviour::register_defaults(&mut registry);
+        registry
+    }
+
+    fn insert(&mut self, block_type: BlockType, properties: BlockProperties) {
+        let index = block_type.0 as usize;
+        if self.blocks.len() <= index {
+            self.blocks.resize_with(index + 1, || None);
+        }
+        if self.blocks[index].is_some() {
+            panic!("block id {} is registered twice", index);
+        }
+        if self.names.insert(properties.name.clone(), block_type).is_some() {
+            panic!("block name {} is registered twice", properties.name);
+        }
+        self.blocks[index] = Some(properties);
+    }
+
+    /// Returns the texture array layer of a texture, adding it if it wasn't used yet
+    fn texture_layer(&mut self, name: &str) -> u32 {
+        match self.textures.iter().position(|it| it == name) {
+            Some(layer) => { layer as u32 }
+            None => {
+                self.textures.push(name.to_string());
+                (self.textures.len() - 1) as u32
+            }
+        }
+    }
+
+    /// Looks up a block by name, panics if it doesn't exist
+    pub fn get(&self, name: &str) -> BlockType {
+        *self.names.get(name).unwrap_or_else(|| panic!("unknown block {}", name))
+    }
+
+    /// Looks up a block by name, `None` if it doesn't exist
+    pub fn find(&self, name: &str) -> Option<BlockType> {
+        self.names.get(name).copied()
+    }
+
+    /// Every registered block type, air included, in id order
+    pub fn block_types(&self) -> Vec<BlockType> {
+        self.blocks.iter().enumerate()
+            .filter(|(_, block)| block.is_some())
+            .map(|(id, _)| BlockType(id as u16))
+            .collect()
+    }
+
+    pub fn properties(&self, block_type: BlockType) -> &BlockProperties {
+        self.blocks.get(block_type.0 as usize)
+            .and_then(|it| it.as_ref())
+            .unwrap_or_else(|| panic!("unknown block id {}", block_type.0))
+    }
+
+    pub fn transparency(&self, block_type: BlockType) -> Transparency {
+        self.properties(block_type).transparency
+    }
+
+    pub fn is_fluid(&self, block_type: BlockType) -> bool {
+        self.properties(block_type).fluid
+    }
+
+    pub fn behaviour(&self, block_type: BlockType) -> BlockBehaviour {
+        self.properties(block_type).behaviour
+    }
+
+    pub fn set_behaviour(&mut self, block_type: BlockType, behaviour: BlockBehaviour) {
+        self.blocks[block_type.0 as usize].as_mut()
+            .unwrap_or_else(|| panic!("unknown block id {}", block_type.0))
+            .behaviour = behaviour;
+    }
+
+    /// Texture array layer of a block face, faces are ordered the same as the mesher's (top, bottom, right, left, front, back)
+    pub fn get_texture_id(&self, block_type: BlockType, face: usize) -> u32 {
+        self.properties(block_type).textures[face]
+    }
+
+    /// Paths of every texture used by a block, in texture array layer order
+    pub fn texture_paths(&self) -> Vec<String> {
+        self.textures.iter().map(|name| format!("{}/{}.png", TEXTURE_DIRECTORY, name)).collect()
+    }
+}
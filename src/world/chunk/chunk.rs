@@ -1,6 +1,7 @@
-use std::cmp::PartialEq;
 use ultraviolet::Vec3;
 use crate::render::chunk_renderer::DrawElementsIndirectCommand;
+use crate::world::block::block::Block;
+use crate::world::light::LightStorage;
 
 /// Default chunk size
 pub const CS: usize = 32;
@@ -9,14 +10,23 @@ pub const CS_F32: f32 = CS as f32;
 
 pub struct Chunk {
     pub blocks: Vec<Block>,
-    pub draw_commands: Vec<DrawElementsIndirectCommand>
+    pub light: LightStorage,
+    pub draw_commands: Vec<DrawElementsIndirectCommand>,
+    pub translucent_draw_commands: Vec<DrawElementsIndirectCommand>,
+    pub fluid_draw_commands: Vec<DrawElementsIndirectCommand>,
+    /// the uploaded mesh is out of date (or there is none yet)
+    pub dirty: bool,
 }
 
 impl Chunk {
     pub fn create(blocks: Vec<Block>) -> Chunk {
         Chunk {
             blocks,
+            light: LightStorage::new(),
             draw_commands: Vec::with_capacity(6),
+            translucent_draw_commands: Vec::new(),
+            fluid_draw_commands: Vec::new(),
+            dirty: true,
         }
     }
 
@@ -24,27 +34,31 @@ impl Chunk {
         self.draw_commands.push(draw_command)
     }
 
+    pub fn add_translucent_draw_command(&mut self, draw_command: DrawElementsIndirectCommand) {
+        self.translucent_draw_commands.push(draw_command)
+    }
+
+    pub fn add_fluid_draw_command(&mut self, draw_command: DrawElementsIndirectCommand) {
+        self.fluid_draw_commands.push(draw_command)
+    }
+
     pub fn get_block_at(&self, x: usize, y: usize, z: usize) -> Option<&Block> {
-        if x < 0 || x >= CS || y < 0 || y >= CS || z < 0 || z >= CS {
+        if x >= CS || y >= CS || z >= CS {
             return None;
         }
         let index = x + y * CS + z * CS * CS;
         self.blocks.get(index)
     }
+}
 
-    pub fn is_air(&self, x: usize, y: usize, z: usize) -> bool {
-        if x > CS - 1 || y > CS - 1 || z > CS - 1 {
-            return true;
-        }
-        match self.get_block_at(x, y, z) {
-            None => { true } // assume its true automatically
-            Some(block) => { block.block_type == BlockType::AIR }
-        }
-    }
+/// Index into `Chunk::blocks` of a world block position, relative to the chunk containing it
+pub fn local_index(x: i32, y: i32, z: i32) -> usize {
+    let (x, y, z) = (x.rem_euclid(CS_I32), y.rem_euclid(CS_I32), z.rem_euclid(CS_I32));
+    (x + y * CS_I32 + z * CS_I32 * CS_I32) as usize
 }
 
 #[repr(C)]
-#[derive(Hash, Eq, PartialEq)]
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
 pub struct ChunkPosition {
     pub x: i32,
     pub y: i32,
@@ -52,6 +66,15 @@ pub struct ChunkPosition {
 }
 
 impl ChunkPosition {
+    /// Position of the chunk containing the world block position
+    pub fn containing(x: i32, y: i32, z: i32) -> ChunkPosition {
+        ChunkPosition {
+            x: x.div_euclid(CS_I32),
+            y: y.div_euclid(CS_I32),
+            z: z.div_euclid(CS_I32),
+        }
+    }
+
     pub fn to_vec3(&self) -> Vec3 {
         Vec3::new(
             self.x as f32,
@@ -64,24 +87,3 @@ impl ChunkPosition {
         self.to_vec3() * CS_F32
     }
 }
-
-#[derive(Copy, Clone)]
-pub struct Block {
-    pub block_type: BlockType,
-}
-
-#[derive(PartialEq, Clone, Copy)]
-pub enum BlockType {
-    AIR,
-    DIRT,
-    COBBLESTONE,
-}
-
-// todo: add faces for stuff like grass
-fn get_texture_id(block_type: BlockType) -> u32 {
-    match block_type {
-        BlockType::AIR => { panic!("Should not be possible to be air") } // Should not be possible
-        BlockType::DIRT => { 0 }
-        BlockType::COBBLESTONE => { 1 }
-    }
-}
\ No newline at end of file
@@ -1,261 +1,321 @@
-use crate::world::chunk::chunk::{Chunk, CS};
-
-// todo: binary baked ao greedy meshing
-pub fn greedy_mesh(chunk: &Chunk) -> Vec<Vec<u64>> {
-    let mut vertices: Vec<Vec<u64>> = vec![
-        Vec::new(),
-        Vec::new(),
-        Vec::new(),
-        Vec::new(),
-        Vec::new(),
-        Vec::new(),
-    ];
-
-    // top
-    for y in 0..CS {
-        let mut visited = vec![false; CS * CS];
-
-        #[inline]
-        fn is_visible(chunk: &Chunk, x: usize, y: usize, z: usize) -> bool {
-            !chunk.is_air(x, y, z) && chunk.is_air(x, y + 1, z)
-        }
+use crate::world::block::block::{BlockType, Transparency};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{local_index, ChunkPosition, CS, CS_I32};
+use crate::world::light::LightChannel;
+use crate::world::world::World;
+
+/// Number of u64s every quad takes up in the mesh
+pub const QUAD_WORDS: usize = 2;
+
+/// Quads of a chunk, split per face direction (indexed the same as `FACES`)
+pub struct ChunkMesh {
+    pub opaque: Vec<Vec<u64>>,
+    pub translucent: Vec<Vec<u64>>,
+    pub fluid: Vec<Vec<u64>>,
+}
 
-        for x in 0..CS {
-            for z in 0..CS {
-                if is_visible(chunk, x, y, z) && !visited[x + z * CS] {
-                    let mut w = 1;
-                    let mut d = 1;
+/// How a face direction is laid out, matches the `wDir`, `hDir` and `flipLookup` logic in shader.vert
+struct FaceLayout {
+    normal: usize,
+    positive: bool,
+    w_axis: usize,
+    h_axis: usize,
+    flip: bool,
+}
 
-                    while x + w < CS && is_visible(chunk, x + w, y, z) && !visited[(x + w) + z * CS] {
-                        w += 1;
-                    }
+const FACES: [FaceLayout; 6] = [
+    // top
+    FaceLayout { normal: 1, positive: true, w_axis: 0, h_axis: 2, flip: false },
+    // bottom
+    FaceLayout { normal: 1, positive: false, w_axis: 0, h_axis: 2, flip: true },
+    // right
+    FaceLayout { normal: 0, positive: true, w_axis: 1, h_axis: 2, flip: false },
+    // left
+    FaceLayout { normal: 0, positive: false, w_axis: 1, h_axis: 2, flip: false },
+    // front
+    FaceLayout { normal: 2, positive: false, w_axis: 0, h_axis: 1, flip: true },
+    // back
+    FaceLayout { normal: 2, positive: true, w_axis: 0, h_axis: 1, flip: false },
+];
+
+/// Blocks and light of a chunk with a one block border taken from its neighbours,
+/// so faces on the chunk border can be culled and lit without looking up other chunks
+struct PaddedChunk {
+    blocks: Vec<BlockType>,
+    light: Vec<u16>,
+    /// fluid levels, see `Block::level`
+    levels: Vec<u8>,
+}
 
-                    'outer: while z + d < CS {
-                        for i in 0..w {
-                            if chunk.is_air(x + i, y, z + d) || visited[(x + i) + (z + d) * CS] {
-                                break 'outer;
+const PADDED: usize = CS + 2;
+
+impl PaddedChunk {
+    fn create(world: &World, position: ChunkPosition) -> PaddedChunk {
+        let chunk = &world.chunks[&position];
+        let mut padded = PaddedChunk {
+            blocks: vec![BlockType::AIR; PADDED * PADDED * PADDED],
+            light: vec![0; PADDED * PADDED * PADDED],
+            levels: vec![0; PADDED * PADDED * PADDED],
+        };
+
+        // the border of every neighbour is copied in one go, so each chunk only gets looked up once
+        let range = |offset: i32| match offset {
+            -1 => { -1..0 }
+            0 => { 0..CS_I32 }
+            _ => { CS_I32..CS_I32 + 1 }
+        };
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbour = world.chunks.get(&ChunkPosition { x: position.x + dx, y: position.y + dy, z: position.z + dz });
+                    for x in range(dx) {
+                        for y in range(dy) {
+                            for z in range(dz) {
+                                let index = padded_index([x, y, z]);
+                                match neighbour {
+                                    Some(neighbour) => {
+                                        let local = local_index(x, y, z);
+                                        padded.blocks[index] = neighbour.blocks[local].block_type;
+                                        padded.levels[index] = neighbour.blocks[local].level;
+                                        padded.light[index] = neighbour.light.packed(local);
+                                    }
+                                    // unloaded blocks are air, lit the same as the closest block of this chunk
+                                    None => {
+                                        let clamp = |it: i32| it.clamp(0, CS_I32 - 1);
+                                        padded.light[index] = chunk.light.packed(local_index(clamp(x), clamp(y), clamp(z)));
+                                    }
+                                }
                             }
                         }
-                        d += 1;
                     }
-                    for dx in 0..w {
-                        for dz in 0..d {
-                            visited[(x + dx) + (z + dz) * CS] = true;
-                        }
-                    }
-
-                    vertices[0].push(pack_data(x, y + 1, z, w, d, 0));
                 }
             }
         }
+        padded
     }
 
-    // bottom
-    for y in 0..CS {
-        let mut visited = vec![false; CS * CS];
-
-        #[inline]
-        fn is_visible(chunk: &Chunk, x: usize, y: usize, z: usize) -> bool {
-            !chunk.is_air(x, y, z) && chunk.is_air(x, y - 1, z)
-        }
-
-        for x in 0..CS {
-            for z in 0..CS {
-                if is_visible(chunk, x, y, z) && !visited[x + z * CS] {
-                    let mut w = 1;
-                    let mut d = 1;
-
-                    while x + w < CS && is_visible(chunk, x + w, y, z) && !visited[(x + w) + z * CS] {
-                        w += 1;
-                    }
-
-                    'outer: while z + d < CS {
-                        for i in 0..w {
-                            if chunk.is_air(x + i, y, z + d) || visited[(x + i) + (z + d) * CS] {
-                                break 'outer;
-                            }
-                        }
-                        d += 1;
-                    }
-                    for dx in 0..w {
-                        for dz in 0..d {
-                            visited[(x + dx) + (z + dz) * CS] = true;
-                        }
-                    }
+    #[inline]
+    fn block(&self, pos: [i32; 3]) -> BlockType {
+        self.blocks[padded_index(pos)]
+    }
 
-                    vertices[1].push(pack_data(x + w, y, z, w, d, 0));
-                }
-            }
-        }
+    #[inline]
+    fn light(&self, pos: [i32; 3]) -> u16 {
+        self.light[padded_index(pos)]
     }
 
-    // right
-    for x in 0..CS {
-        let mut visited = vec![false; CS * CS];
+    #[inline]
+    fn level(&self, pos: [i32; 3]) -> u8 {
+        self.levels[padded_index(pos)]
+    }
 
-        #[inline]
-        fn is_visible(chunk: &Chunk, x: usize, y: usize, z: usize) -> bool {
-            !chunk.is_air(x, y, z) && chunk.is_air(x + 1, y, z)
+    /// Level of the fluid at `pos` if it is the top of the fluid, so its surface is inside the block
+    fn fluid_surface(&self, registry: &BlockRegistry, pos: [i32; 3]) -> Option<u8> {
+        let block_type = self.block(pos);
+        let mut above = pos;
+        above[1] += 1;
+        if registry.is_fluid(block_type) && self.block(above) != block_type {
+            Some(self.level(pos))
+        } else {
+            None
         }
+    }
+}
 
-        for y in 0..CS {
-            for z in 0..CS {
-                if is_visible(chunk, x, y, z) && !visited[z + y * CS] {
-                    let mut w = 1;
-                    let mut d = 1;
-
-                    while z + w < CS && is_visible(chunk, x, y, z + w) && !visited[(z + w) + y * CS] {
-                        w += 1;
-                    }
-
-                    'outer: while y + d < CS {
-                        for i in 0..w {
-                            if chunk.is_air(x, y + d, z + i) || visited[(z + i) + (y + d) * CS] {
-                                break 'outer;
-                            }
-                        }
-                        d += 1;
-                    }
+/// Index into a `PaddedChunk` of a position relative to the chunk, -1 and `CS` are the neighbour's blocks
+#[inline]
+fn padded_index(pos: [i32; 3]) -> usize {
+    (pos[0] + 1) as usize + (pos[1] + 1) as usize * PADDED + (pos[2] + 1) as usize * PADDED * PADDED
+}
 
-                    for dx in 0..w {
-                        for dz in 0..d {
-                            visited[(z + dx) + (y + dz) * CS] = true;
-                        }
-                    }
+/// Everything that has to match for two faces to get merged into one quad
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FaceKey {
+    block_type: BlockType,
+    /// ambient occlusion of each corner, 2 bits each, 3 is unoccluded
+    ao: u8,
+    /// packed light of each corner, see `LightStorage`
+    light: [u16; 4],
+    /// fluid level if the top edge of the face is at the fluid surface instead of the top of the block
+    surface: Option<u8>,
+}
 
-                    vertices[2].push(pack_data(x + 1, y, z, d, w, 0));
+// todo: binary greedy meshing
+pub fn greedy_mesh(world: &World, position: ChunkPosition) -> ChunkMesh {
+    let registry = &world.registry;
+    let padded = PaddedChunk::create(world, position);
+
+    let mut mesh = ChunkMesh {
+        opaque: vec![Vec::new(); 6],
+        translucent: vec![Vec::new(); 6],
+        fluid: vec![Vec::new(); 6],
+    };
+
+    // every visible face in the current slice, cleared as faces get merged
+    let mut mask: Vec<Option<FaceKey>> = vec![None; CS * CS];
+
+    for (face, layout) in FACES.iter().enumerate() {
+        for layer in 0..CS {
+            for v in 0..CS {
+                for u in 0..CS {
+                    let mut pos = [0; 3];
+                    pos[layout.normal] = layer as i32;
+                    pos[layout.w_axis] = u as i32;
+                    pos[layout.h_axis] = v as i32;
+
+                    let mut neighbour = pos;
+                    neighbour[layout.normal] += if layout.positive { 1 } else { -1 };
+
+                    let block_type = padded.block(pos);
+                    let surface = padded.fluid_surface(registry, pos);
+                    // fluid next to more of itself with a lower surface shows the step between them
+                    let fluid_step = layout.normal != 1 && padded.block(neighbour) == block_type
+                        && matches!((surface, padded.fluid_surface(registry, neighbour)), (Some(own), Some(other)) if other > own);
+
+                    mask[u + v * CS] = if fluid_step || is_face_visible(registry, block_type, padded.block(neighbour)) {
+                        let mut key = face_key(registry, &padded, layout, block_type, neighbour);
+                        // bottom faces have no top edge to lower
+                        key.surface = if face == 1 { None } else { surface };
+                        Some(key)
+                    } else {
+                        None
+                    };
                 }
             }
-        }
-    }
 
-    // left
-    for x in 0..CS {
-        let mut visited = vec![false; CS * CS];
-
-        #[inline]
-        fn is_visible(chunk: &Chunk, x: usize, y: usize, z: usize) -> bool {
-            !chunk.is_air(x, y, z) && chunk.is_air(x - 1, y, z)
-        }
+            for v in 0..CS {
+                let mut u = 0;
+                while u < CS {
+                    let Some(key) = mask[u + v * CS] else {
+                        u += 1;
+                        continue;
+                    };
 
-        for y in 0..CS {
-            for z in 0..CS {
-                if is_visible(chunk, x, y, z) && !visited[z + y * CS] {
                     let mut w = 1;
-                    let mut d = 1;
+                    let mut h = 1;
 
-                    while z + w < CS && is_visible(chunk, x, y, z + w) && !visited[(z + w) + y * CS] {
+                    while u + w < CS && mask[(u + w) + v * CS] == Some(key) {
                         w += 1;
                     }
 
-                    'outer: while y + d < CS {
+                    'outer: while v + h < CS {
                         for i in 0..w {
-                            if chunk.is_air(x, y + d, z + i) || visited[(z + i) + (y + d) * CS] {
+                            if mask[(u + i) + (v + h) * CS] != Some(key) {
                                 break 'outer;
                             }
                         }
-                        d += 1;
+                        h += 1;
                     }
 
-                    for width in 0..w {
-                        for depth in 0..d {
-                            visited[(z + width) + (y + depth) * CS] = true;
+                    for dv in 0..h {
+                        for du in 0..w {
+                            mask[(u + du) + (v + dv) * CS] = None;
                         }
                     }
-                    vertices[3].push(pack_data(x, y, z, d, w, 0));
-                }
-            }
-        }
-    }
 
-    // front
-    for z in 0..CS {
-        let mut visited = vec![false; CS * CS];
-
-        #[inline]
-        fn is_visible(chunk: &Chunk, x: usize, y: usize, z: usize) -> bool {
-            !chunk.is_air(x, y, z) && chunk.is_air(x, y, z - 1)
-        }
-
-        for y in 0..CS {
-            for x in 0..CS {
-                if is_visible(chunk, x, y, z) && !visited[(x + y * CS)] {
-                    let mut w = 1;
-                    let mut d = 1;
-
-
-                    while z + w < CS && is_visible(chunk, x + w, y, z) && !visited[(x + w) + y * CS] {
-                        w += 1;
+                    let mut origin = [0; 3];
+                    origin[layout.normal] = layer + layout.positive as usize;
+                    origin[layout.w_axis] = if layout.flip { u + w } else { u };
+                    origin[layout.h_axis] = v;
+
+                    let texture_id = registry.get_texture_id(key.block_type, face) as u8;
+                    let quad = pack_data(origin[0], origin[1], origin[2], w, h, texture_id, key.ao, key.light, key.surface);
+                    if registry.is_fluid(key.block_type) {
+                        mesh.fluid[face].extend_from_slice(&quad);
+                    } else if registry.transparency(key.block_type) == Transparency::Translucent {
+                        mesh.translucent[face].extend_from_slice(&quad);
+                    } else {
+                        mesh.opaque[face].extend_from_slice(&quad);
                     }
 
-                    'outer: while y + d < CS {
-                        for i in 0..w {
-                            if chunk.is_air(x + i, y + d, z) || visited[(x + i) + (y + d) * CS] {
-                                break 'outer;
-                            }
-                        }
-                        d += 1;
-                    }
-
-                    for dx in 0..w {
-                        for dz in 0..d {
-                            visited[(x + dx) + (y + dz) * CS] = true;
-                        }
-                    }
-
-                    vertices[4].push(pack_data(x + w, y, z, w, d, 0));
+                    u += w;
                 }
             }
         }
     }
+    mesh
+}
 
-    // back
-    for z in 0..CS {
-        let mut visited = vec![false; CS * CS];
-
-        #[inline]
-        fn is_visible(chunk: &Chunk, x: usize, y: usize, z: usize) -> bool {
-            !chunk.is_air(x, y, z) && chunk.is_air(x, y, z + 1)
+/// Samples ambient occlusion and smooth light for the corners of a face. `front` is the block the face looks into,
+/// corners are in the same order as the vertices in shader.vert (`vertexID = wMod * 2 + hMod`)
+fn face_key(registry: &BlockRegistry, padded: &PaddedChunk, layout: &FaceLayout, block_type: BlockType, front: [i32; 3]) -> FaceKey {
+    let is_opaque = |pos: [i32; 3]| registry.transparency(padded.block(pos)).is_opaque();
+
+    let mut ao = 0;
+    let mut light = [0; 4];
+    for (corner, corner_light) in light.iter_mut().enumerate() {
+        // flipped faces start at the far end of the w axis
+        let du = if (corner >> 1 == 1) != layout.flip { 1 } else { -1 };
+        let dv = if corner & 1 == 1 { 1 } else { -1 };
+
+        let mut side_u = front;
+        side_u[layout.w_axis] += du;
+        let mut side_v = front;
+        side_v[layout.h_axis] += dv;
+        let mut diagonal = side_u;
+        diagonal[layout.h_axis] += dv;
+
+        let (side_u_opaque, side_v_opaque, diagonal_opaque) = (is_opaque(side_u), is_opaque(side_v), is_opaque(diagonal));
+        let corner_ao = if side_u_opaque && side_v_opaque {
+            0
+        } else {
+            3 - (side_u_opaque as u8 + side_v_opaque as u8 + diagonal_opaque as u8)
+        };
+        ao |= corner_ao << (corner * 2);
+
+        // light only gets averaged over blocks it can be in, the diagonal is hidden if both sides are opaque
+        let mut samples = [padded.light(front); 4];
+        let mut count = 1;
+        for (pos, visible) in [(side_u, !side_u_opaque), (side_v, !side_v_opaque), (diagonal, !diagonal_opaque && corner_ao > 0)] {
+            if visible {
+                samples[count] = padded.light(pos);
+                count += 1;
+            }
         }
+        *corner_light = average_light(&samples[..count]);
+    }
 
-        for y in 0..CS {
-            for x in 0..CS {
-                if is_visible(chunk, x, y, z) && !visited[x + y * CS] {
-                    let mut w = 1;
-                    let mut d = 1;
-
-                    while x + w < CS && is_visible(chunk, x + w, y, z) && !visited[(x + w) + y * CS] {
-                        w += 1;
-                    }
-
-                    'outer: while y + d < CS {
-                        for i in 0..w {
-                            if chunk.is_air(x + i, y + d, z) || visited[(x + i) + (y + d) * CS] {
-                                break 'outer;
-                            }
-                        }
-                        d += 1;
-                    }
+    FaceKey { block_type, ao, light, surface: None }
+}
 
-                    for dx in 0..w {
-                        for dz in 0..d {
-                            visited[(x + dx) + (y + dz) * CS] = true;
-                        }
-                    }
+/// Averages every channel of packed light values on its own, rounded to the nearest level
+fn average_light(samples: &[u16]) -> u16 {
+    let count = samples.len() as u16;
+    LightChannel::ALL.iter().fold(0, |average, channel| {
+        let shift = channel.shift();
+        let sum: u16 = samples.iter().map(|it| (it >> shift) & 0xF).sum();
+        average | (((sum + count / 2) / count) << shift)
+    })
+}
 
-                    vertices[5].push(pack_data(x, y, z + 1, w, d, 0));
-                }
-            }
-        }
+/// Opaque neighbours hide every face, translucent blocks only hide faces of the same type
+/// so that water doesn't render the faces between its own blocks, but glass next to water does
+#[inline]
+fn is_face_visible(registry: &BlockRegistry, block_type: BlockType, neighbour_type: BlockType) -> bool {
+    if block_type == BlockType::AIR || registry.transparency(neighbour_type).is_opaque() {
+        return false;
     }
-    vertices
+    !(registry.transparency(block_type) == Transparency::Translucent && block_type == neighbour_type)
 }
 
-fn pack_data(x: usize, y: usize, z: usize, width: usize, height: usize, texture_id: u8) -> u64 {
-    (x as u64) |
-    ((y as u64) << 6) |
-    ((z as u64) << 12) |
-    ((width as u64) << 18) |
-    ((height as u64) << 24) |
-    ((texture_id as u64) << 30)
+/// A quad takes two u64s, the first one holds its position, size, texture, ao and fluid surface level,
+/// the second one the light of each corner
+#[allow(clippy::too_many_arguments)]
+fn pack_data(x: usize, y: usize, z: usize, width: usize, height: usize, texture_id: u8, ao: u8, light: [u16; 4], surface: Option<u8>) -> [u64; QUAD_WORDS] {
+    // one bit whether the quad is at a fluid surface, then 3 bits for its level
+    let surface = surface.map_or(0, |level| 1 | ((level as u64) << 1));
+    [
+        (x as u64) |
+        ((y as u64) << 6) |
+        ((z as u64) << 12) |
+        ((width as u64) << 18) |
+        ((height as u64) << 24) |
+        ((texture_id as u64) << 30) |
+        ((ao as u64) << 38) |
+        (surface << 46),
+        (light[0] as u64) |
+        ((light[1] as u64) << 16) |
+        ((light[2] as u64) << 32) |
+        ((light[3] as u64) << 48)
+    ]
 }
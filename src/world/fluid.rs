@@ -0,0 +1,228 @@
+use crate::world::block::block::BlockType;
+use crate::world::world::World;
+
+type BlockPos = [i32; 3];
+
+/// Ticks between two steps of a flowing fluid
+pub const FLOW_DELAY: u64 = 5;
+/// Fluids flow at most this many blocks away from a source
+pub const MAX_LEVEL: u8 = 7;
+/// How far the surface of a source sits below the top of its block, matches FLUID_SURFACE_DROP in shader.vert
+pub const FLUID_SURFACE_DROP: f32 = 0.125;
+
+const HORIZONTAL: [[i32; 3]; 4] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+
+/// Height of the surface of a fluid inside its block, it drops by an eighth for every level. Matches shader.vert
+pub fn surface_height(level: u8) -> f32 {
+    (1.0 - FLUID_SURFACE_DROP) * (8 - level.min(MAX_LEVEL)) as f32 / 8.0
+}
+
+/// Something changed next to the fluid, it gets updated after `FLOW_DELAY`
+pub fn schedule(world: &mut World, pos: BlockPos) {
+    world.schedule_update(pos, FLOW_DELAY);
+}
+
+/// One step of the fluid at `pos`. Flowing fluid first takes its level from its neighbours (or dries up),
+/// then the fluid falls into the block below and spreads sideways one level further.
+/// Every block it changes schedules the fluids around it again, so only fluid that is still moving gets updated
+pub fn update(world: &mut World, [x, y, z]: BlockPos) {
+    let fluid = world.get_block(x, y, z);
+    if !world.registry.is_fluid(fluid) {
+        return;
+    }
+
+    let mut level = world.get_level(x, y, z);
+    if level != 0 {
+        match flowing_level(world, [x, y, z], fluid) {
+            None => {
+                world.set_block(x, y, z, BlockType::AIR);
+                return;
+            }
+            Some(new_level) => {
+                if new_level != level {
+                    world.set_fluid(x, y, z, fluid, new_level);
+                    level = new_level;
+                }
+            }
+        }
+    }
+
+    let can_fall = can_flow_into(world, [x, y - 1, z], fluid, 1);
+    if can_fall {
+        world.set_fluid(x, y - 1, z, fluid, 1);
+    }
+
+    // sources always spread, flowing fluid only once it rests on something that isn't more of itself
+    let resting = !can_fall && world.get_block(x, y - 1, z) != fluid;
+    if level < MAX_LEVEL && (level == 0 || resting) {
+        for [dx, _, dz] in HORIZONTAL {
+            let neighbour = [x + dx, y, z + dz];
+            if can_flow_into(world, neighbour, fluid, level + 1) {
+                world.set_fluid(neighbour[0], y, neighbour[2], fluid, level + 1);
+            }
+        }
+    }
+}
+
+/// Level a flowing fluid should have, `None` if nothing feeds it anymore.
+/// Fluid with more of itself above is falling and always level 1. Like in Minecraft, a block between
+/// two sources becomes a source itself if it rests on something solid or on another source
+fn flowing_level(world: &World, [x, y, z]: BlockPos, fluid: BlockType) -> Option<u8> {
+    if world.get_block(x, y + 1, z) == fluid {
+        return Some(1);
+    }
+
+    let mut sources = 0;
+    let mut lowest: Option<u8> = None;
+    for [dx, _, dz] in HORIZONTAL {
+        if world.get_block(x + dx, y, z + dz) == fluid {
+            let level = world.get_level(x + dx, y, z + dz);
+            if level == 0 {
+                sources += 1;
+            }
+            lowest = Some(lowest.map_or(level, |lowest| lowest.min(level)));
+        }
+    }
+
+    let below = world.get_block(x, y - 1, z);
+    let supported = world.registry.properties(below).solid || (below == fluid && world.get_level(x, y - 1, z) == 0);
+    if sources >= 2 && supported {
+        return Some(0);
+    }
+    lowest.map(|level| level + 1).filter(|level| *level <= MAX_LEVEL)
+}
+
+/// Air can always be flowed into, the same fluid only if it is further away from a source than `level`.
+/// Unloaded blocks never are
+fn can_flow_into(world: &World, [x, y, z]: BlockPos, fluid: BlockType, level: u8) -> bool {
+    match world.get_loaded_block(x, y, z) {
+        Some(BlockType::AIR) => { true }
+        Some(block_type) if block_type == fluid => {
+            let current = world.get_level(x, y, z);
+            current != 0 && current > level
+        }
+        _ => { false }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::block::{Block, BlockType};
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::{Chunk, ChunkPosition, CS};
+    use crate::world::settings::WorldSettings;
+    use crate::world::world::World;
+
+    /// One chunk with a dirt floor at y 0, everything around it is unloaded
+    fn flat_world() -> World {
+        let mut world = World::new(BlockRegistry::load("resources/blocks.ron"), WorldSettings::default());
+        let dirt = world.registry.get("dirt");
+        let mut chunk = Chunk::create(vec![Block::new(BlockType::AIR); CS * CS * CS]);
+        for x in 0..CS {
+            for z in 0..CS {
+                chunk.blocks[x + z * CS * CS] = Block::new(dirt);
+            }
+        }
+        world.add_chunk(ChunkPosition { x: 0, y: 0, z: 0 }, chunk);
+        world
+    }
+
+    /// Ticks until no fluid is moving anymore
+    fn settle(world: &mut World) {
+        let mut ticks = 0;
+        while world.pending_updates() > 0 {
+            world.tick();
+            ticks += 1;
+            assert!(ticks < 10_000, "fluid never settled");
+        }
+    }
+
+    fn water_at(world: &World, x: i32, y: i32, z: i32) -> Option<u8> {
+        (world.get_block(x, y, z) == world.registry.get("water")).then(|| world.get_level(x, y, z))
+    }
+
+    #[test]
+    fn spreads_one_level_per_block() {
+        let mut world = flat_world();
+        let water = world.registry.get("water");
+        world.set_block(16, 1, 16, water);
+        settle(&mut world);
+
+        for distance in 0..=7 {
+            assert_eq!(water_at(&world, 16 + distance, 1, 16), Some(distance as u8));
+            assert_eq!(water_at(&world, 16, 1, 16 - distance), Some(distance as u8));
+        }
+        assert_eq!(water_at(&world, 24, 1, 16), None);
+        assert_eq!(water_at(&world, 19, 1, 20), Some(7));
+        assert_eq!(water_at(&world, 20, 1, 20), None);
+        assert_eq!(water_at(&world, 16, 2, 16), None);
+    }
+
+    #[test]
+    fn falls_before_spreading() {
+        let mut world = flat_world();
+        let water = world.registry.get("water");
+        world.set_block(8, 6, 8, water);
+        settle(&mut world);
+
+        for y in 1..6 {
+            assert_eq!(water_at(&world, 8, y, 8), Some(1));
+        }
+        // the source spreads one block sideways, that falls down too
+        assert_eq!(water_at(&world, 9, 6, 8), Some(1));
+        assert_eq!(water_at(&world, 9, 3, 8), Some(1));
+        // falling fluid only spreads once it lands
+        assert_eq!(water_at(&world, 10, 3, 8), None);
+        assert_eq!(water_at(&world, 15, 1, 8), Some(7));
+        assert_eq!(water_at(&world, 16, 1, 8), None);
+    }
+
+    #[test]
+    fn two_sources_make_a_new_one() {
+        let mut world = flat_world();
+        let water = world.registry.get("water");
+        world.set_block(10, 1, 10, water);
+        world.set_block(12, 1, 10, water);
+        settle(&mut world);
+
+        assert_eq!(water_at(&world, 11, 1, 10), Some(0));
+        assert_eq!(water_at(&world, 11, 1, 11), Some(1));
+    }
+
+    #[test]
+    fn dries_up_without_a_source() {
+        let mut world = flat_world();
+        let water = world.registry.get("water");
+        world.set_block(16, 1, 16, water);
+        settle(&mut world);
+        world.set_block(16, 1, 16, BlockType::AIR);
+        settle(&mut world);
+
+        let chunk = &world.chunks[&ChunkPosition { x: 0, y: 0, z: 0 }];
+        assert!(chunk.blocks.iter().all(|block| block.block_type != water));
+    }
+
+    #[test]
+    fn is_deterministic() {
+        let run = || {
+            let mut world = flat_world();
+            let water = world.registry.get("water");
+            let dirt = world.registry.get("dirt");
+            world.set_block(12, 1, 9, dirt);
+            world.set_block(10, 4, 10, water);
+            world.set_block(14, 1, 12, water);
+            for _ in 0..40 {
+                world.tick();
+            }
+            world.set_block(10, 4, 10, BlockType::AIR);
+            settle(&mut world);
+            world.chunks[&ChunkPosition { x: 0, y: 0, z: 0 }].blocks.clone()
+        };
+        assert_eq!(run(), run());
+    }
+}
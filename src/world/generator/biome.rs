@@ -0,0 +1,250 @@
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use serde::{Deserialize, Serialize};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::generator::terrain::TerrainBlocks;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Biome {
+    Plains,
+    Desert,
+    Mountains,
+    Ocean,
+    Snow,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [
+        Biome::Plains,
+        Biome::Desert,
+        Biome::Mountains,
+        Biome::Ocean,
+        Biome::Snow,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Biome::Plains => { "plains" }
+            Biome::Desert => { "desert" }
+            Biome::Mountains => { "mountains" }
+            Biome::Ocean => { "ocean" }
+            Biome::Snow => { "snow" }
+        }
+    }
+
+    /// Temperature and humidity the biome is at home in, the closest biome to the climate of a column wins
+    pub fn climate(&self) -> (f32, f32) {
+        match self {
+            Biome::Plains => { (0.1, -0.1) }
+            Biome::Desert => { (0.6, -0.5) }
+            Biome::Mountains => { (-0.2, -0.5) }
+            Biome::Ocean => { (0.2, 0.6) }
+            Biome::Snow => { (-0.6, 0.1) }
+        }
+    }
+
+    pub fn terrain(&self) -> BiomeTerrain {
+        let (height, roughness) = match self {
+            Biome::Plains => { (0.0, 0.15) }
+            Biome::Desert => { (6.0, 0.12) }
+            Biome::Mountains => { (50.0, 1.0) }
+            Biome::Ocean => { (-56.0, 0.1) }
+            Biome::Snow => { (20.0, 0.4) }
+        };
+        BiomeTerrain { height, roughness }
+    }
+
+    /// Surface block and the blocks right below it, below those it's all cobblestone
+    pub fn blocks(&self, registry: &BlockRegistry) -> TerrainBlocks {
+        let (surface, subsurface) = match self {
+            Biome::Plains => { ("grass", "dirt") }
+            Biome::Desert => { ("sand", "sand") }
+            Biome::Mountains => { ("cobblestone", "cobblestone") }
+            Biome::Ocean => { ("sand", "sand") }
+            Biome::Snow => { ("snow", "dirt") }
+        };
+        TerrainBlocks {
+            surface: registry.get(surface),
+            subsurface: registry.get(subsurface),
+            filler: registry.get("cobblestone"),
+            water: registry.get("water"),
+        }
+    }
+}
+
+/// Shape of the terrain in a biome, added to the generator's own settings
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BiomeTerrain {
+    /// added to the base height
+    pub height: f32,
+    /// multiplies the amplitude of the height noise and the overhangs
+    pub roughness: f32,
+}
+
+impl Default for BiomeTerrain {
+    fn default() -> BiomeTerrain {
+        BiomeTerrain { height: 0.0, roughness: 1.0 }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeSettings {
+    pub enabled: bool,
+    /// frequency of the temperature and humidity noise, lower makes bigger biomes
+    pub frequency: f32,
+    /// how far apart in climate two biomes still get mixed, higher makes wider borders
+    pub blend: f32,
+}
+
+impl Default for BiomeSettings {
+    fn default() -> BiomeSettings {
+        BiomeSettings {
+            enabled: true,
+            frequency: 0.0015,
+            blend: 0.1,
+        }
+    }
+}
+
+/// Picks the biome of every column from temperature and humidity noise
+pub struct BiomeMap {
+    temperature: FastNoiseLite,
+    humidity: FastNoiseLite,
+    blend: f32,
+}
+
+impl BiomeMap {
+    pub fn new(seed: i32, settings: &BiomeSettings) -> BiomeMap {
+        let noise = |seed: i32| {
+            let mut noise = FastNoiseLite::with_seed(seed);
+            noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+            noise.set_frequency(Some(settings.frequency));
+            noise.set_fractal_type(Some(FractalType::FBm));
+            noise.set_fractal_octaves(Some(2));
+            noise
+        };
+        BiomeMap {
+            temperature: noise(seed.wrapping_add(10)),
+            humidity: noise(seed.wrapping_add(11)),
+            blend: settings.blend,
+        }
+    }
+
+    pub fn climate(&self, x: i32, z: i32) -> (f32, f32) {
+        (self.temperature.get_noise_2d(x as f32, z as f32), self.humidity.get_noise_2d(x as f32, z as f32))
+    }
+
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        self.sample(x, z).0
+    }
+
+    /// The biome of a column, and the terrain of every biome close enough in climate mixed together,
+    /// so the height changes smoothly across biome borders
+    pub fn sample(&self, x: i32, z: i32) -> (Biome, BiomeTerrain) {
+        let (temperature, humidity) = self.climate(x, z);
+        let distances = Biome::ALL.map(|biome| {
+            let (t, h) = biome.climate();
+            ((t - temperature).powi(2) + (h - humidity).powi(2)).sqrt()
+        });
+        let (closest, closest_distance) = distances.iter().copied().enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+
+        // relative to the closest biome, which always gets a weight of 1
+        let mut terrain = BiomeTerrain { height: 0.0, roughness: 0.0 };
+        let mut total = 0.0;
+        for (biome, distance) in Biome::ALL.iter().zip(distances) {
+            let weight = (-(distance - closest_distance) / self.blend).exp();
+            let biome_terrain = biome.terrain();
+            terrain.height += biome_terrain.height * weight;
+            terrain.roughness += biome_terrain.roughness * weight;
+            total += weight;
+        }
+        terrain.height /= total;
+        terrain.roughness /= total;
+        (Biome::ALL[closest], terrain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::block::BlockType;
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::{local_index, ChunkPosition, CS_I32};
+    use crate::world::generator::biome::Biome;
+    use crate::world::generator::fractal::{FractalGenerator, FractalSettings};
+    use crate::world::generator::terrain::{GeneratorType, TerrainGenerator};
+    use crate::world::settings::WorldSettings;
+    use crate::world::streaming::{MAX_CHUNK_Y, MIN_CHUNK_Y};
+    use crate::world::world::World;
+
+    const SEED: i32 = 8008135;
+    /// A column in every biome for `SEED`
+    const BIOMES: [(Biome, i32, i32); 5] = [
+        (Biome::Plains, 0, 0),
+        (Biome::Desert, 0, 128),
+        (Biome::Mountains, -160, 32),
+        (Biome::Ocean, 160, -32),
+        (Biome::Snow, 0, -96),
+    ];
+
+    fn generator(registry: &BlockRegistry) -> FractalGenerator {
+        FractalGenerator::new(registry, SEED, FractalSettings::default(), 14)
+    }
+
+    #[test]
+    fn biomes_only_depend_on_the_seed() {
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        let settings = WorldSettings { seed: SEED, generator: GeneratorType::Fractal.default_settings(), ..WorldSettings::default() };
+        let world = World::new(registry, settings);
+        for (biome, x, z) in BIOMES {
+            assert_eq!(world.biome_at(x, z), biome, "biome at {} {}", x, z);
+        }
+
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        let settings = WorldSettings { seed: SEED, generator: GeneratorType::Superflat.default_settings(), ..WorldSettings::default() };
+        assert_eq!(World::new(registry, settings).biome_at(-160, 32), Biome::Plains);
+    }
+
+    #[test]
+    fn height_is_smooth_across_biome_borders() {
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        let generator = generator(&registry);
+        let mut borders = 0;
+        for z in (-1024..1024).step_by(32) {
+            for x in -1024..1024 {
+                let (biome, terrain) = generator.biome(x, z);
+                let (next_biome, next_terrain) = generator.biome(x + 1, z);
+                if biome == next_biome {
+                    continue;
+                }
+                borders += 1;
+                // the noise on top can still be steep in rough terrain, the part the biomes add is what gets blended
+                let step = (terrain.height - next_terrain.height).abs();
+                assert!(step <= 4.0, "{} blocks high step from {:?} to {:?} at {} {}", step, biome, next_biome, x, z);
+            }
+        }
+        assert!(borders > 0);
+    }
+
+    #[test]
+    fn biomes_are_built_from_their_own_blocks() {
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        let generator = generator(&registry);
+        for (biome, x, z) in BIOMES {
+            let blocks = biome.blocks(&registry);
+            let column: Vec<BlockType> = (MIN_CHUNK_Y..=MAX_CHUNK_Y)
+                .flat_map(|chunk_y| {
+                    let chunk = generator.generate(ChunkPosition { x: x.div_euclid(CS_I32), y: chunk_y, z: z.div_euclid(CS_I32) });
+                    (0..CS_I32).map(move |y| chunk.blocks[local_index(x, y, z)].block_type)
+                })
+                .collect();
+            let water = column.iter().rposition(|block_type| *block_type != BlockType::AIR).unwrap();
+            let top = column.iter().rposition(|block_type| *block_type != BlockType::AIR && *block_type != blocks.water).unwrap();
+            // under water the surface block is left out
+            let surface = if top == water { blocks.surface } else { blocks.subsurface };
+            assert!(column[top] == surface, "{:?} has the wrong surface", biome);
+            assert!(column[top - 1] == blocks.subsurface, "{:?} has the wrong subsurface", biome);
+        }
+    }
+}
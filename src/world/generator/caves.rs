@@ -0,0 +1,69 @@
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use serde::{Deserialize, Serialize};
+use crate::world::chunk::chunk::ChunkPosition;
+use crate::world::generator::terrain::NoiseLattice;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaveSettings {
+    /// big open caverns are carved wherever the cheese noise is above this, 1.0 turns them off
+    pub cheese_threshold: f32,
+    pub cheese_frequency: f32,
+    /// winding tunnels follow the lines where two noises are both closer to zero than this, 0.0 turns them off
+    pub spaghetti_thickness: f32,
+    pub spaghetti_frequency: f32,
+    /// in columns near or under the sea caves stay this many blocks below the ground,
+    /// so they don't open up next to the water
+    pub min_depth: i32,
+}
+
+impl Default for CaveSettings {
+    fn default() -> CaveSettings {
+        CaveSettings {
+            cheese_threshold: 0.55,
+            cheese_frequency: 0.02,
+            spaghetti_thickness: 0.08,
+            spaghetti_frequency: 0.015,
+            min_depth: 8,
+        }
+    }
+}
+
+/// Carves caves out of the terrain with 3D noise
+pub struct Caves {
+    cheese: FastNoiseLite,
+    spaghetti: [FastNoiseLite; 2],
+    pub settings: CaveSettings,
+}
+
+impl Caves {
+    pub fn new(seed: i32, settings: CaveSettings) -> Caves {
+        let noise = |seed: i32, frequency: f32| {
+            let mut noise = FastNoiseLite::with_seed(seed);
+            noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+            noise.set_frequency(Some(frequency));
+            noise
+        };
+        Caves {
+            cheese: noise(seed.wrapping_add(1), settings.cheese_frequency),
+            spaghetti: [
+                noise(seed.wrapping_add(2), settings.spaghetti_frequency),
+                noise(seed.wrapping_add(3), settings.spaghetti_frequency),
+            ],
+            settings,
+        }
+    }
+
+    /// Positive inside a cave
+    pub fn density(&self, x: f32, y: f32, z: f32) -> f32 {
+        // stretched vertically so caverns are wider than they are tall
+        let cheese = self.cheese.get_noise_3d(x, y * 2.0, z) - self.settings.cheese_threshold;
+        let tunnel = self.settings.spaghetti_thickness - self.spaghetti[0].get_noise_3d(x, y, z).abs()
+            .max(self.spaghetti[1].get_noise_3d(x, y, z).abs());
+        cheese.max(tunnel)
+    }
+
+    pub fn lattice(&self, position: ChunkPosition) -> NoiseLattice {
+        NoiseLattice::sample(position, |x, y, z| self.density(x, y, z))
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use crate::world::block::block::BlockType;
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{Chunk, ChunkPosition, CS, CS_I32};
+use crate::world::tick;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum FeatureKind {
+    /// a log trunk with a blob of leaves on top
+    Tree,
+    /// a ball of cobblestone half sunk into the ground
+    Boulder,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FeatureSettings {
+    pub kind: FeatureKind,
+    /// name of the block the feature grows on
+    pub ground: String,
+    /// chance of the feature in every column with the ground block on top
+    pub chance: f64,
+}
+
+/// Features placed on top of the terrain, each column gets at most one, the first one in the list that is rolled
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecorationSettings {
+    pub features: Vec<FeatureSettings>,
+}
+
+impl Default for DecorationSettings {
+    fn default() -> DecorationSettings {
+        let feature = |kind: FeatureKind, ground: &str, chance: f64| FeatureSettings { kind, ground: String::from(ground), chance };
+        DecorationSettings {
+            features: vec![
+                feature(FeatureKind::Tree, "grass", 0.012),
+                feature(FeatureKind::Tree, "snow", 0.004),
+                feature(FeatureKind::Boulder, "cobblestone", 0.004),
+                feature(FeatureKind::Boulder, "grass", 0.0008),
+            ],
+        }
+    }
+}
+
+/// A block placed by a feature, in world coordinates
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FeatureBlock {
+    pub position: [i32; 3],
+    pub block_type: BlockType,
+}
+
+/// Places features on generated columns of chunks. Features are rooted in the column that is decorated but can reach
+/// a few blocks into its neighbours, where they only go once those columns are generated too
+pub struct Decorator {
+    seed: i64,
+    /// kind, ground block and chance
+    features: Vec<(FeatureKind, BlockType, f64)>,
+    log: BlockType,
+    leaves: BlockType,
+    boulder: BlockType,
+}
+
+impl Decorator {
+    pub fn new(registry: &BlockRegistry, seed: i32, settings: &DecorationSettings) -> Decorator {
+        Decorator {
+            seed: seed as i64,
+            features: settings.features.iter()
+                .map(|feature| (feature.kind, registry.get(&feature.ground), feature.chance))
+                .collect(),
+            log: registry.get("log"),
+            leaves: registry.get("leaves"),
+            boulder: registry.get("cobblestone"),
+        }
+    }
+
+    /// Every block of the features rooted in a column of chunks, `chunks` goes from the lowest chunk up. Features grow
+    /// on the highest block of each block column only, not on the floor of a cave or under an overhang. What a column
+    /// gets only depends on the seed, its position and its own blocks, never on which columns were generated before
+    pub fn decorate(&self, chunks: &[(ChunkPosition, Chunk)]) -> Vec<FeatureBlock> {
+        let mut blocks = Vec::new();
+        if self.features.is_empty() {
+            return blocks;
+        }
+        for z in 0..CS {
+            for x in 0..CS {
+                let Some((position, ground, y)) = chunks.iter().rev().find_map(|(position, chunk)| {
+                    (0..CS).rev()
+                        .map(|y| (chunk.blocks[x + y * CS + z * CS * CS].block_type, y))
+                        .find(|(block_type, _)| *block_type != BlockType::AIR)
+                        .map(|(block_type, y)| (position, block_type, y))
+                }) else { continue };
+                let root = [
+                    position.x * CS_I32 + x as i32,
+                    position.y * CS_I32 + y as i32,
+                    position.z * CS_I32 + z as i32,
+                ];
+
+                for (index, (kind, feature_ground, chance)) in self.features.iter().enumerate() {
+                    if *feature_ground != ground {
+                        continue;
+                    }
+                    let random = tick::hash(&[self.seed, root[0] as i64, root[2] as i64, index as i64]);
+                    // the top 53 bits as a number from 0 to 1
+                    if (random >> 11) as f64 / (1u64 << 53) as f64 >= *chance {
+                        continue;
+                    }
+                    match kind {
+                        FeatureKind::Tree => { self.tree(root, random, &mut blocks) }
+                        FeatureKind::Boulder => { self.boulder(root, random, &mut blocks) }
+                    }
+                    break;
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Whether a feature block goes where `existing` is. Only air and the blocks of other features are replaced,
+    /// by whichever has the higher priority, so overlapping features end up the same no matter which one is placed first
+    pub fn replaces(&self, existing: BlockType, block_type: BlockType) -> bool {
+        self.priority(block_type) > self.priority(existing)
+    }
+
+    fn priority(&self, block_type: BlockType) -> u8 {
+        if block_type == BlockType::AIR {
+            0
+        } else if block_type == self.leaves {
+            1
+        } else if block_type == self.log {
+            2
+        } else {
+            u8::MAX
+        }
+    }
+
+    fn tree(&self, [x, y, z]: [i32; 3], random: u64, blocks: &mut Vec<FeatureBlock>) {
+        let height = 4 + (random % 3) as i32;
+        for dy in 1..=height {
+            blocks.push(FeatureBlock { position: [x, y + dy, z], block_type: self.log });
+        }
+        for dy in height - 1..=height + 2 {
+            let radius: i32 = if dy <= height { 2 } else { 1 };
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    // corners are rounded off, the lower ones only sometimes
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    if corner && (radius == 1 || tick::hash(&[random as i64, dx as i64, dy as i64, dz as i64]).is_multiple_of(2)) {
+                        continue;
+                    }
+                    blocks.push(FeatureBlock { position: [x + dx, y + dy, z + dz], block_type: self.leaves });
+                }
+            }
+        }
+    }
+
+    fn boulder(&self, [x, y, z]: [i32; 3], random: u64, blocks: &mut Vec<FeatureBlock>) {
+        let radius = 1 + (random % 2) as i32;
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                for dz in -radius..=radius {
+                    if dx * dx + dy * dy + dz * dz <= radius * radius + 1 {
+                        blocks.push(FeatureBlock { position: [x + dx, y + dy, z + dz], block_type: self.boulder });
+                    }
+                }
+            }
+        }
+    }
+}
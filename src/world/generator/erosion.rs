@@ -0,0 +1,375 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use crate::world::chunk::chunk::{ChunkPosition, CS_I32};
+use crate::world::tick;
+
+/// Width of the square of columns eroded at once, a whole number of chunks
+pub const REGION_SIZE: i32 = 128;
+/// Columns eroded around a region, so droplets near its edge have somewhere to come from and go to
+const REGION_MARGIN: i32 = 16;
+const PADDED_SIZE: i32 = REGION_SIZE + 2 * REGION_MARGIN;
+/// Over this many columns from the edge of a region the changes of the droplets fade out, neighbouring regions are
+/// eroded separately and would otherwise not line up
+const FADE: f32 = 16.0;
+const MAX_CACHED_REGIONS: usize = 16;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErosionSettings {
+    pub enabled: bool,
+    /// droplets per region, together with `max_steps` this bounds how long eroding a region takes
+    pub droplets: u32,
+    /// how far a droplet runs at most
+    pub max_steps: u32,
+    /// how much a droplet keeps going the way it was going instead of straight downhill
+    pub inertia: f32,
+    /// how much sediment a droplet carries per speed, water and drop in height
+    pub capacity: f32,
+    pub min_capacity: f32,
+    /// how much of the missing sediment a droplet picks up per step
+    pub erosion: f32,
+    /// how much of the sediment above its capacity a droplet drops per step
+    pub deposition: f32,
+    pub evaporation: f32,
+    pub gravity: f32,
+    /// river sources tried per region, only the rivers that reach the sea without leaving the region are kept
+    pub rivers: u32,
+    pub river_width: f32,
+    pub river_depth: f32,
+    /// rivers only start at least this high above the sea
+    pub river_min_height: f32,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> ErosionSettings {
+        ErosionSettings {
+            enabled: false,
+            droplets: 4000,
+            max_steps: 32,
+            inertia: 0.05,
+            capacity: 4.0,
+            min_capacity: 0.01,
+            erosion: 0.3,
+            deposition: 0.3,
+            evaporation: 0.02,
+            gravity: 4.0,
+            rivers: 8,
+            river_width: 2.5,
+            river_depth: 3.0,
+            river_min_height: 12.0,
+        }
+    }
+}
+
+/// Heights of a region after erosion and rivers, only the region itself without the margin
+pub struct ErodedRegion {
+    origin: (i32, i32),
+    heights: Vec<i32>,
+    /// river surface of every column, `i32::MIN` where there is no river above the sea
+    water: Vec<i32>,
+}
+
+impl ErodedRegion {
+    /// Ground height and river surface of a column, the column has to be in the region
+    pub fn column(&self, x: i32, z: i32) -> (i32, Option<i32>) {
+        let index = ((x - self.origin.0) + (z - self.origin.1) * REGION_SIZE) as usize;
+        let water = self.water[index];
+        (self.heights[index], (water != i32::MIN).then_some(water))
+    }
+}
+
+/// Droplet based hydraulic erosion and river carving, done per region of columns before chunks get filled.
+/// Everything is seeded by the region's position, so a region comes out the same no matter when it's eroded
+pub struct Erosion {
+    pub settings: ErosionSettings,
+    seed: i64,
+    sea_level: i32,
+    regions: RefCell<HashMap<(i32, i32), Rc<ErodedRegion>>>,
+}
+
+/// Height of a padded heightmap and its slope, interpolated between columns
+fn sample(heights: &[f32], x: f32, z: f32) -> (f32, f32, f32) {
+    let (ix, iz) = (x as i32, z as i32);
+    let (fx, fz) = (x - ix as f32, z - iz as f32);
+    let index = (ix + iz * PADDED_SIZE) as usize;
+    let (h00, h10) = (heights[index], heights[index + 1]);
+    let (h01, h11) = (heights[index + PADDED_SIZE as usize], heights[index + PADDED_SIZE as usize + 1]);
+    let height = h00 * (1.0 - fx) * (1.0 - fz) + h10 * fx * (1.0 - fz) + h01 * (1.0 - fx) * fz + h11 * fx * fz;
+    let slope_x = (h10 - h00) * (1.0 - fz) + (h11 - h01) * fz;
+    let slope_z = (h01 - h00) * (1.0 - fx) + (h11 - h10) * fx;
+    (height, slope_x, slope_z)
+}
+
+/// Adds to the four columns around a position, weighted by how close it is to each
+fn spread(heights: &mut [f32], x: f32, z: f32, amount: f32) {
+    let (ix, iz) = (x as i32, z as i32);
+    let (fx, fz) = (x - ix as f32, z - iz as f32);
+    let index = (ix + iz * PADDED_SIZE) as usize;
+    heights[index] += amount * (1.0 - fx) * (1.0 - fz);
+    heights[index + 1] += amount * fx * (1.0 - fz);
+    heights[index + PADDED_SIZE as usize] += amount * (1.0 - fx) * fz;
+    heights[index + PADDED_SIZE as usize + 1] += amount * fx * fz;
+}
+
+impl Erosion {
+    pub fn new(seed: i32, settings: ErosionSettings, sea_level: i32) -> Erosion {
+        Erosion {
+            settings,
+            seed: seed as i64,
+            sea_level,
+            regions: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The eroded region a chunk is in, `height` gives the height of a column before erosion
+    pub fn region(&self, position: ChunkPosition, height: impl Fn(i32, i32) -> f32) -> Rc<ErodedRegion> {
+        let chunks_per_region = REGION_SIZE / CS_I32;
+        let key = (position.x.div_euclid(chunks_per_region), position.z.div_euclid(chunks_per_region));
+        if let Some(region) = self.regions.borrow().get(&key) {
+            return region.clone();
+        }
+
+        let region = Rc::new(self.erode(key, height));
+        let mut regions = self.regions.borrow_mut();
+        if regions.len() >= MAX_CACHED_REGIONS {
+            let farthest = regions.keys()
+                .copied()
+                .max_by_key(|(x, z)| (x - key.0).pow(2) + (z - key.1).pow(2))
+                .unwrap();
+            regions.remove(&farthest);
+        }
+        regions.insert(key, region.clone());
+        region
+    }
+
+    fn erode(&self, key: (i32, i32), height: impl Fn(i32, i32) -> f32) -> ErodedRegion {
+        let origin = (key.0 * REGION_SIZE, key.1 * REGION_SIZE);
+        let padded_origin = (origin.0 - REGION_MARGIN, origin.1 - REGION_MARGIN);
+        let mut base = Vec::with_capacity((PADDED_SIZE * PADDED_SIZE) as usize);
+        for z in 0..PADDED_SIZE {
+            for x in 0..PADDED_SIZE {
+                base.push(height(padded_origin.0 + x, padded_origin.1 + z));
+            }
+        }
+
+        let mut heights = base.clone();
+        self.run_droplets(key, &mut heights);
+        for z in 0..PADDED_SIZE {
+            for x in 0..PADDED_SIZE {
+                // the margin is only there for the droplets, it keeps the height from before
+                let (x, z) = (x - REGION_MARGIN, z - REGION_MARGIN);
+                let edge = x.min(z).min(REGION_SIZE - 1 - x).min(REGION_SIZE - 1 - z).max(0) as f32;
+                let fade = (edge / FADE).min(1.0);
+                let index = ((x + REGION_MARGIN) + (z + REGION_MARGIN) * PADDED_SIZE) as usize;
+                heights[index] = base[index] + (heights[index] - base[index]) * fade;
+            }
+        }
+        let water = self.carve_rivers(key, &mut heights);
+
+        let mut region = ErodedRegion {
+            origin,
+            heights: Vec::with_capacity((REGION_SIZE * REGION_SIZE) as usize),
+            water: Vec::with_capacity((REGION_SIZE * REGION_SIZE) as usize),
+        };
+        for z in 0..REGION_SIZE {
+            for x in 0..REGION_SIZE {
+                let index = ((x + REGION_MARGIN) + (z + REGION_MARGIN) * PADDED_SIZE) as usize;
+                let height = heights[index] as i32;
+                let water = water[index].map_or(i32::MIN, |level| level as i32);
+                region.heights.push(height);
+                region.water.push(if water > height && water > self.sea_level { water } else { i32::MIN });
+            }
+        }
+        region
+    }
+
+    /// Lets droplets run down the terrain, they pick up ground where they speed up and drop it where they slow down
+    fn run_droplets(&self, key: (i32, i32), heights: &mut [f32]) {
+        let settings = &self.settings;
+        let max = (PADDED_SIZE - 1) as f32;
+        for droplet in 0..settings.droplets {
+            let random = tick::hash(&[self.seed, key.0 as i64, key.1 as i64, droplet as i64]);
+            let mut x = (random & 0xFFFF) as f32 / 65536.0 * max;
+            let mut z = ((random >> 16) & 0xFFFF) as f32 / 65536.0 * max;
+            let (mut direction_x, mut direction_z) = (0.0, 0.0);
+            let (mut speed, mut water, mut sediment) = (1.0f32, 1.0f32, 0.0f32);
+
+            for _ in 0..settings.max_steps {
+                let (height, slope_x, slope_z) = sample(heights, x, z);
+                direction_x = direction_x * settings.inertia - slope_x * (1.0 - settings.inertia);
+                direction_z = direction_z * settings.inertia - slope_z * (1.0 - settings.inertia);
+                let length = (direction_x * direction_x + direction_z * direction_z).sqrt();
+                if length < f32::EPSILON {
+                    break;
+                }
+                direction_x /= length;
+                direction_z /= length;
+
+                let (old_x, old_z) = (x, z);
+                x += direction_x;
+                z += direction_z;
+                if x < 0.0 || z < 0.0 || x >= max || z >= max {
+                    break;
+                }
+
+                let height_difference = sample(heights, x, z).0 - height;
+                let capacity = (-height_difference * speed * water * settings.capacity).max(settings.min_capacity);
+                if sediment > capacity || height_difference > 0.0 {
+                    // uphill it fills the hole it's leaving
+                    let amount = if height_difference > 0.0 {
+                        height_difference.min(sediment)
+                    } else {
+                        (sediment - capacity) * settings.deposition
+                    };
+                    sediment -= amount;
+                    spread(heights, old_x, old_z, amount);
+                } else {
+                    // never digs deeper than the drop, that would leave a pit behind
+                    let amount = ((capacity - sediment) * settings.erosion).min(-height_difference);
+                    sediment += amount;
+                    spread(heights, old_x, old_z, -amount);
+                }
+                speed = (speed * speed - height_difference * settings.gravity).max(0.0).sqrt();
+                water *= 1.0 - settings.evaporation;
+            }
+        }
+    }
+
+    /// Follows the steepest way down from a few high sources to the sea, cutting a channel on the way. Rivers stay
+    /// inside the region, the neighbouring regions don't know about them, and the ones that don't make it to the sea
+    /// are left out. Returns the river surface of every column of the padded region
+    fn carve_rivers(&self, key: (i32, i32), heights: &mut [f32]) -> Vec<Option<f32>> {
+        let settings = &self.settings;
+        let mut water: Vec<Option<f32>> = vec![None; heights.len()];
+        let sea_level = self.sea_level as f32;
+        let reach = settings.river_width.ceil() as i32;
+        // columns the middle of a river can be in so all of it is inside the region
+        let (min, max) = (REGION_MARGIN + reach, REGION_MARGIN + REGION_SIZE - reach);
+        if max <= min {
+            return water;
+        }
+
+        for river in 0..settings.rivers {
+            let random = tick::hash(&[self.seed, key.0 as i64, key.1 as i64, -1 - river as i64]);
+            let mut x = min + (random % (max - min) as u64) as i32;
+            let mut z = min + ((random >> 32) % (max - min) as u64) as i32;
+            let mut level = heights[(x + z * PADDED_SIZE) as usize];
+            if level < sea_level + settings.river_min_height {
+                continue;
+            }
+
+            let mut path = Vec::new();
+            let mut visited = HashSet::new();
+            // long enough to cross the region a few times, the path can't go on forever
+            for _ in 0..PADDED_SIZE * 4 {
+                visited.insert((x, z));
+                // rivers only ever go down, through any bump in the way
+                level = level.min(heights[(x + z * PADDED_SIZE) as usize]);
+                path.push((x, z, level));
+                if level <= sea_level {
+                    break;
+                }
+
+                let next = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)].iter()
+                    .map(|(dx, dz)| (x + dx, z + dz))
+                    .filter(|(x, z)| *x >= min && *z >= min && *x < max && *z < max && !visited.contains(&(*x, *z)))
+                    .min_by(|a, b| heights[(a.0 + a.1 * PADDED_SIZE) as usize].total_cmp(&heights[(b.0 + b.1 * PADDED_SIZE) as usize]));
+                let Some(next) = next else { break };
+                (x, z) = next;
+            }
+            if level > sea_level {
+                continue;
+            }
+
+            for (x, z, level) in path {
+                for dz in -reach..=reach {
+                    for dx in -reach..=reach {
+                        let distance = ((dx * dx + dz * dz) as f32).sqrt();
+                        if distance > settings.river_width {
+                            continue;
+                        }
+                        let index = ((x + dx) + (z + dz) * PADDED_SIZE) as usize;
+                        let bed = level - settings.river_depth * (1.0 - (distance / settings.river_width).powi(2));
+                        heights[index] = heights[index].min(bed);
+                        water[index] = Some(water[index].map_or(level, |surface: f32| surface.max(level)));
+                    }
+                }
+            }
+        }
+
+        // the surface can't be higher than the banks next to it, the water would just be hanging there
+        let mut surfaces = water.clone();
+        for z in 0..PADDED_SIZE {
+            for x in 0..PADDED_SIZE {
+                let index = (x + z * PADDED_SIZE) as usize;
+                let Some(mut surface) = water[index] else { continue };
+                for dz in -1..=1 {
+                    for dx in -1..=1 {
+                        let (nx, nz) = (x + dx, z + dz);
+                        if nx < 0 || nz < 0 || nx >= PADDED_SIZE || nz >= PADDED_SIZE {
+                            continue;
+                        }
+                        let neighbour = (nx + nz * PADDED_SIZE) as usize;
+                        if water[neighbour].is_none() {
+                            surface = surface.min(heights[neighbour]);
+                        }
+                    }
+                }
+                surfaces[index] = Some(surface);
+            }
+        }
+        surfaces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::chunk::chunk::ChunkPosition;
+    use crate::world::generator::erosion::{Erosion, ErosionSettings, REGION_SIZE};
+
+    /// A hill in the middle of the sea
+    fn hill(x: i32, z: i32) -> f32 {
+        80.0 - ((x * x + z * z) as f32).sqrt() * 0.5
+    }
+
+    fn erosion() -> Erosion {
+        Erosion::new(1234, ErosionSettings { enabled: true, rivers: 8, ..ErosionSettings::default() }, 14)
+    }
+
+    #[test]
+    fn regions_do_not_depend_on_order() {
+        let (first, second) = (erosion(), erosion());
+        let (a, b) = (ChunkPosition { x: 0, y: 0, z: 0 }, ChunkPosition { x: -4, y: 0, z: 0 });
+        let (first_a, first_b) = (first.region(a, hill), first.region(b, hill));
+        let (second_b, second_a) = (second.region(b, hill), second.region(a, hill));
+        assert!(first_a.heights == second_a.heights && first_a.water == second_a.water);
+        assert!(first_b.heights == second_b.heights && first_b.water == second_b.water);
+    }
+
+    #[test]
+    fn rivers_run_above_sea_level() {
+        let region = erosion().region(ChunkPosition { x: 0, y: 0, z: 0 }, hill);
+        let mut rivers = 0;
+        let mut mouths = 0;
+        for z in 0..REGION_SIZE {
+            for x in 0..REGION_SIZE {
+                let (height, water) = region.column(x, z);
+                let Some(water) = water else { continue };
+                assert!(water > height && water > 14);
+                rivers += 1;
+
+                // the river flows into the sea next to it
+                let sea = (-1..=1).flat_map(|dz| (-1..=1).map(move |dx| (x + dx, z + dz)))
+                    .filter(|(x, z)| (0..REGION_SIZE).contains(x) && (0..REGION_SIZE).contains(z))
+                    .any(|(x, z)| region.column(x, z).1.is_none() && region.column(x, z).0 < 14);
+                if sea {
+                    mouths += 1;
+                }
+            }
+        }
+        assert!(rivers > 0);
+        assert!(mouths > 0, "no river reaches the sea");
+    }
+}
@@ -0,0 +1,214 @@
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use serde::{Deserialize, Serialize};
+use crate::world::block::block::Block;
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{ChunkPosition, Chunk, CS, CS_I32};
+use crate::world::generator::biome::{Biome, BiomeMap, BiomeSettings, BiomeTerrain};
+use crate::world::generator::caves::{CaveSettings, Caves};
+use crate::world::generator::decoration::DecorationSettings;
+use crate::world::generator::erosion::{Erosion, ErosionSettings};
+use crate::world::generator::ores::{OreSettings, Ores};
+use crate::world::generator::terrain::{empty_chunk, NoiseKind, NoiseLattice, TerrainBlocks, TerrainGenerator};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FractalSettings {
+    pub noise: NoiseKind,
+    /// number of noise layers added together, each one adds finer detail
+    pub octaves: i32,
+    /// frequency of the first octave
+    pub frequency: f32,
+    /// how much the frequency grows from one octave to the next
+    pub lacunarity: f32,
+    /// how much the amplitude shrinks from one octave to the next
+    pub gain: f32,
+    /// height the terrain is centered around
+    pub base_height: i32,
+    /// how far the terrain goes above and below `base_height`
+    pub amplitude: f32,
+    /// how many blocks 3D noise can push the ground up or down, this is what makes overhangs and arches
+    pub overhang: f32,
+    pub overhang_frequency: f32,
+    pub caves: CaveSettings,
+    pub biomes: BiomeSettings,
+    pub erosion: ErosionSettings,
+    pub ores: Vec<OreSettings>,
+    pub decoration: DecorationSettings,
+}
+
+impl Default for FractalSettings {
+    fn default() -> FractalSettings {
+        FractalSettings {
+            noise: NoiseKind::OpenSimplex2,
+            octaves: 6,
+            frequency: 0.002,
+            lacunarity: 2.0,
+            gain: 0.5,
+            base_height: 48,
+            amplitude: 160.0,
+            overhang: 12.0,
+            overhang_frequency: 0.03,
+            caves: CaveSettings::default(),
+            biomes: BiomeSettings::default(),
+            erosion: ErosionSettings::default(),
+            ores: OreSettings::defaults(),
+            decoration: DecorationSettings::default(),
+        }
+    }
+}
+
+/// Fractal Brownian motion heightmap, large landforms with smaller bumps on top of them.
+/// A block is ground when its density, the distance below the height plus some 3D noise, is positive,
+/// and caves are carved out of that. Biomes change the height and roughness of the terrain and what it's made of,
+/// and the heightmap can be eroded and cut by rivers before any of that
+pub struct FractalGenerator {
+    noise: FastNoiseLite,
+    overhang: FastNoiseLite,
+    caves: Caves,
+    biomes: Option<BiomeMap>,
+    erosion: Option<Erosion>,
+    settings: FractalSettings,
+    sea_level: i32,
+    /// indexed by biome
+    blocks: Vec<TerrainBlocks>,
+    ores: Ores,
+}
+
+impl FractalGenerator {
+    pub fn new(registry: &BlockRegistry, seed: i32, settings: FractalSettings, sea_level: i32) -> FractalGenerator {
+        let mut noise = FastNoiseLite::with_seed(seed);
+        noise.set_noise_type(Some(settings.noise.noise_type()));
+        noise.set_frequency(Some(settings.frequency));
+        noise.set_fractal_type(Some(FractalType::FBm));
+        noise.set_fractal_octaves(Some(settings.octaves));
+        noise.set_fractal_lacunarity(Some(settings.lacunarity));
+        noise.set_fractal_gain(Some(settings.gain));
+
+        let mut overhang = FastNoiseLite::with_seed(seed.wrapping_sub(1));
+        overhang.set_noise_type(Some(NoiseType::OpenSimplex2));
+        overhang.set_frequency(Some(settings.overhang_frequency));
+
+        FractalGenerator {
+            noise,
+            overhang,
+            caves: Caves::new(seed, settings.caves.clone()),
+            biomes: settings.biomes.enabled.then(|| BiomeMap::new(seed, &settings.biomes)),
+            erosion: settings.erosion.enabled.then(|| Erosion::new(seed, settings.erosion.clone(), sea_level)),
+            ores: Ores::new(registry, seed, &settings.ores),
+            settings,
+            sea_level,
+            blocks: Biome::ALL.iter().map(|biome| biome.blocks(registry)).collect(),
+        }
+    }
+
+    /// Biome of a column and how it shapes the terrain, plains everywhere if biomes are turned off
+    pub fn biome(&self, x: i32, z: i32) -> (Biome, BiomeTerrain) {
+        match &self.biomes {
+            Some(biomes) => { biomes.sample(x, z) }
+            None => { (Biome::Plains, BiomeTerrain::default()) }
+        }
+    }
+
+    pub fn height(&self, x: i32, z: i32, terrain: BiomeTerrain) -> i32 {
+        self.settings.base_height + self.relative_height(x, z, terrain) as i32
+    }
+
+    /// Height above `base_height`, before it's rounded
+    fn relative_height(&self, x: i32, z: i32, terrain: BiomeTerrain) -> f32 {
+        let noise = self.noise.get_noise_2d(x as f32, z as f32);
+        terrain.height + noise * self.settings.amplitude * terrain.roughness
+    }
+}
+
+impl TerrainGenerator for FractalGenerator {
+    fn generate(&self, position: ChunkPosition) -> Chunk {
+        let mut chunk = empty_chunk();
+        let bottom = position.y * CS_I32;
+        let region = self.erosion.as_ref().map(|erosion| erosion.region(position, |x, z| {
+            self.settings.base_height as f32 + self.relative_height(x, z, self.biome(x, z).1)
+        }));
+        // height, biome, overhang strength and water surface of every column
+        let mut columns = Vec::with_capacity((CS_I32 * CS_I32) as usize);
+        for z in 0..CS_I32 {
+            for x in 0..CS_I32 {
+                let (world_x, world_z) = (position.x * CS_I32 + x, position.z * CS_I32 + z);
+                let (biome, terrain) = self.biome(world_x, world_z);
+                let (height, river) = match &region {
+                    Some(region) => { region.column(world_x, world_z) }
+                    None => { (self.height(world_x, world_z, terrain), None) }
+                };
+                // overhangs would fill the river bed back up
+                let overhang = if river.is_some() { 0.0 } else { terrain.roughness * self.settings.overhang };
+                columns.push((height, biome, overhang, river.unwrap_or(self.sea_level).max(self.sea_level)));
+            }
+        }
+        let highest = columns.iter().map(|(height, _, overhang, _)| *height as f32 + overhang).fold(f32::MIN, f32::max);
+        let highest_water = columns.iter().map(|(_, _, _, water)| *water).max().unwrap_or(self.sea_level);
+        if bottom as f32 > highest && bottom >= highest_water {
+            return chunk;
+        }
+
+        let overhang = NoiseLattice::sample(position, |x, y, z| self.overhang.get_noise_3d(x, y, z));
+        let caves = self.caves.lattice(position);
+        for z in 0..CS_I32 {
+            for x in 0..CS_I32 {
+                let (height, biome, strength, water) = columns[(x + z * CS_I32) as usize];
+                let blocks = &self.blocks[biome as usize];
+                let (overhang, caves) = (overhang.column(x, z), caves.column(x, z));
+                // one more than the chunk is tall, the block above decides whether this is the surface
+                let mut ground = [false; CS + 1];
+                for (local_y, ground) in ground.iter_mut().enumerate() {
+                    let local_y = local_y as i32;
+                    *ground = (height - bottom - local_y) as f32 + overhang.get(local_y) * strength > 0.0;
+                }
+                let cave_ceiling = if height < water + self.caves.settings.min_depth {
+                    height - self.caves.settings.min_depth
+                } else {
+                    i32::MAX
+                };
+
+                for local_y in 0..CS_I32 {
+                    let y = bottom + local_y;
+                    let block_type = if ground[local_y as usize] {
+                        if y < cave_ceiling && caves.get(local_y) > 0.0 {
+                            continue;
+                        }
+                        blocks.ground(y, height, !ground[local_y as usize + 1], water)
+                    } else if y < water {
+                        blocks.water
+                    } else {
+                        continue;
+                    };
+                    chunk.blocks[(x + local_y * CS_I32 + z * CS_I32 * CS_I32) as usize] = Block::new(block_type);
+                }
+            }
+        }
+        self.ores.place(position, &mut chunk);
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::block::BlockType;
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::ChunkPosition;
+    use crate::world::generator::caves::CaveSettings;
+    use crate::world::generator::fractal::{FractalGenerator, FractalSettings};
+    use crate::world::generator::terrain::TerrainGenerator;
+
+    fn air(settings: FractalSettings) -> usize {
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        let generator = FractalGenerator::new(&registry, 8008135, settings, 14);
+        let chunk = generator.generate(ChunkPosition { x: 0, y: -4, z: 0 });
+        chunk.blocks.iter().filter(|block| block.block_type == BlockType::AIR).count()
+    }
+
+    #[test]
+    fn caves_are_carved_deep_underground() {
+        assert!(air(FractalSettings::default()) > 0);
+
+        let caves = CaveSettings { cheese_threshold: 1.0, spaghetti_thickness: 0.0, ..CaveSettings::default() };
+        assert_eq!(air(FractalSettings { caves, ..FractalSettings::default() }), 0);
+    }
+}
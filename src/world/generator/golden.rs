@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::fs;
+use ron::ser::PrettyConfig;
+use crate::world::block::block::Block;
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{Chunk, ChunkPosition};
+use crate::world::generator::terrain::{GeneratorSettings, GeneratorType};
+use crate::world::settings::WorldSettings;
+use crate::world::streaming::{MAX_CHUNK_Y, MIN_CHUNK_Y};
+use crate::world::tick;
+
+const GOLDEN_PATH: &str = "resources/golden/generation.ron";
+const SEEDS: [i32; 2] = [1, 8008135];
+/// Settings that aren't the default of any generator but still need covering, by name,
+/// with chunks they change that are generated on top of `CHUNKS`
+const EXTRA_SETTINGS: [(&str, &str, &[ChunkPosition]); 1] = [
+    ("eroded fractal", "Fractal(erosion: (enabled: true))", &[
+        // worn down surface
+        ChunkPosition { x: 0, y: 1, z: 0 },
+        ChunkPosition { x: -2, y: 1, z: -1 },
+        // rivers running into the sea, for both seeds
+        ChunkPosition { x: -4, y: 0, z: -6 },
+        ChunkPosition { x: -5, y: 1, z: 5 },
+    ]),
+];
+const CHUNKS: [ChunkPosition; 5] = [
+    ChunkPosition { x: 0, y: 0, z: 0 },
+    ChunkPosition { x: -1, y: -1, z: 2 },
+    ChunkPosition { x: 3, y: 1, z: -2 },
+    ChunkPosition { x: 0, y: -3, z: 0 },
+    // in the middle of an erosion region
+    ChunkPosition { x: 1, y: 1, z: 2 },
+];
+
+fn hash_blocks(blocks: &[Block]) -> u64 {
+    blocks.iter().fold(0, |hash, block| tick::hash(&[hash as i64, block.block_type.0 as i64, block.level as i64]))
+}
+
+/// Hash of every chunk, and of the feature blocks of its column that are inside it, by generator, seed and chunk position
+fn generate() -> BTreeMap<String, String> {
+    let registry = BlockRegistry::load("resources/blocks.ron");
+    let mut hashes = BTreeMap::new();
+    let mut generators: Vec<(&str, GeneratorSettings, &[ChunkPosition])> = GeneratorType::ALL.iter()
+        .map(|generator_type| (generator_type.name(), generator_type.default_settings(), &[] as &[ChunkPosition]))
+        .collect();
+    for (name, text, chunks) in EXTRA_SETTINGS {
+        generators.push((name, GeneratorSettings::parse(text).unwrap_or_else(|err| panic!("{}", err)), chunks));
+    }
+
+    for (name, generator_settings, extra_chunks) in generators {
+        for seed in SEEDS {
+            let settings = WorldSettings { seed, generator: generator_settings.clone(), ..WorldSettings::default() };
+            let generator = settings.create_generator(&registry);
+            let decorator = settings.create_decorator(&registry);
+            for position in CHUNKS.iter().chain(extra_chunks).copied() {
+                // features are rooted per column, the whole column is generated for them
+                let column: Vec<(ChunkPosition, Chunk)> = (MIN_CHUNK_Y..=MAX_CHUNK_Y)
+                    .map(|y| {
+                        let position = ChunkPosition { y, ..position };
+                        (position, generator.generate(position))
+                    })
+                    .collect();
+                let chunk = &column[(position.y - MIN_CHUNK_Y) as usize].1;
+                let features = decorator.decorate(&column).iter()
+                    .filter(|feature| ChunkPosition::containing(feature.position[0], feature.position[1], feature.position[2]) == position)
+                    .fold(0, |hash, feature| {
+                        let [x, y, z] = feature.position;
+                        tick::hash(&[hash as i64, x as i64, y as i64, z as i64, feature.block_type.0 as i64])
+                    });
+                let key = format!("{} {} ({}, {}, {})", name, seed, position.x, position.y, position.z);
+                hashes.insert(key, format!("{:016x} {:016x}", hash_blocks(&chunk.blocks), features));
+            }
+        }
+    }
+    hashes
+}
+
+/// Golden tests for terrain generation. A fixed set of chunks is generated for a few seeds with every generator,
+/// and a hash of each chunk is compared against the ones saved in `GOLDEN_PATH`, so a refactor can't change the
+/// terrain without anyone noticing. When a change to the terrain is on purpose, regenerate the hashes with
+/// `UPDATE_GOLDEN=1 cargo test golden` and commit them together with the change.
+#[test]
+fn generation_matches_golden_hashes() {
+    let hashes = generate();
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        let text = ron::ser::to_string_pretty(&hashes, PrettyConfig::new())
+            .unwrap_or_else(|err| panic!("failed to write golden hashes: {}", err));
+        fs::write(GOLDEN_PATH, text + "\n").unwrap_or_else(|err| panic!("failed to save {}: {}", GOLDEN_PATH, err));
+        return;
+    }
+
+    let text = fs::read_to_string(GOLDEN_PATH)
+        .unwrap_or_else(|err| panic!("failed to read {}: {}, run UPDATE_GOLDEN=1 cargo test golden to create it", GOLDEN_PATH, err));
+    let golden: BTreeMap<String, String> = ron::from_str(&text)
+        .unwrap_or_else(|err| panic!("invalid golden hashes in {}: {}", GOLDEN_PATH, err));
+
+    let changed: Vec<&String> = hashes.keys().chain(golden.keys())
+        .filter(|key| hashes.get(*key) != golden.get(*key))
+        .collect();
+    assert!(
+        changed.is_empty(),
+        "generated terrain changed for {:?}, if that's on purpose run UPDATE_GOLDEN=1 cargo test golden",
+        changed
+    );
+}
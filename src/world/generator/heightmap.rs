@@ -0,0 +1,70 @@
+use fastnoise_lite::FastNoiseLite;
+use serde::{Deserialize, Serialize};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{ChunkPosition, Chunk, CS_I32};
+use crate::world::generator::decoration::DecorationSettings;
+use crate::world::generator::ores::{OreSettings, Ores};
+use crate::world::generator::terrain::{empty_chunk, fill_column, NoiseKind, TerrainBlocks, TerrainGenerator};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightmapSettings {
+    pub noise: NoiseKind,
+    pub frequency: f32,
+    /// the terrain goes from y 0 up to this
+    pub max_height: i32,
+    pub ores: Vec<OreSettings>,
+    pub decoration: DecorationSettings,
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> HeightmapSettings {
+        HeightmapSettings {
+            noise: NoiseKind::Perlin,
+            frequency: 0.01,
+            max_height: 32,
+            ores: OreSettings::defaults(),
+            decoration: DecorationSettings::default(),
+        }
+    }
+}
+
+/// Rolling hills from a single layer of noise, Perlin by default, water fills everything below the sea level
+pub struct HeightmapGenerator {
+    noise: FastNoiseLite,
+    settings: HeightmapSettings,
+    sea_level: i32,
+    blocks: TerrainBlocks,
+    ores: Ores,
+}
+
+impl HeightmapGenerator {
+    pub fn new(registry: &BlockRegistry, seed: i32, settings: HeightmapSettings, sea_level: i32) -> HeightmapGenerator {
+        let mut noise = FastNoiseLite::with_seed(seed);
+        noise.set_noise_type(Some(settings.noise.noise_type()));
+        noise.set_frequency(Some(settings.frequency));
+        HeightmapGenerator {
+            noise,
+            ores: Ores::new(registry, seed, &settings.ores),
+            settings,
+            sea_level,
+            blocks: TerrainBlocks::from_registry(registry),
+        }
+    }
+}
+
+impl TerrainGenerator for HeightmapGenerator {
+    fn generate(&self, position: ChunkPosition) -> Chunk {
+        let mut chunk = empty_chunk();
+        for x in 0..CS_I32 {
+            for z in 0..CS_I32 {
+                let (world_x, world_z) = (position.x * CS_I32 + x, position.z * CS_I32 + z);
+                let height = (self.noise.get_noise_2d(world_x as f32, world_z as f32) + 1.0) / 2.0;
+                let height = (height * self.settings.max_height as f32) as i32;
+                fill_column(&mut chunk, position, x, z, height, self.sea_level, &self.blocks);
+            }
+        }
+        self.ores.place(position, &mut chunk);
+        chunk
+    }
+}
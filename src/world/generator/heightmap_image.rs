@@ -0,0 +1,199 @@
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use serde::{Deserialize, Serialize};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{ChunkPosition, Chunk, CS_I32};
+use crate::world::generator::decoration::DecorationSettings;
+use crate::world::generator::terrain::{empty_chunk, fill_column, TerrainBlocks, TerrainGenerator};
+use crate::world::generator::ores::{OreSettings, Ores};
+
+/// What the terrain is past the edges of the image
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ImageEdge {
+    /// the image repeats
+    Tile,
+    /// noise takes over, mixed with the pixels on the edge so there is no wall
+    Noise,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageSettings {
+    /// grayscale image, the brighter a pixel the higher the ground. Colored images are turned into grayscale
+    pub path: String,
+    /// blocks per pixel, the image is centered on x and z 0
+    pub scale: f32,
+    /// how much higher a white pixel is than a black one
+    pub height: f32,
+    /// height of a black pixel
+    pub offset: i32,
+    pub surface: String,
+    pub subsurface: String,
+    pub filler: String,
+    pub edge: ImageEdge,
+    pub noise_frequency: f32,
+    /// how many blocks past the edge it takes for the noise to fully take over
+    pub blend: f32,
+    pub ores: Vec<OreSettings>,
+    pub decoration: DecorationSettings,
+}
+
+impl Default for ImageSettings {
+    fn default() -> ImageSettings {
+        ImageSettings {
+            path: String::from("resources/heightmaps/island.png"),
+            scale: 4.0,
+            height: 96.0,
+            offset: -16,
+            surface: String::from("grass"),
+            subsurface: String::from("dirt"),
+            filler: String::from("cobblestone"),
+            edge: ImageEdge::Noise,
+            noise_frequency: 0.004,
+            blend: 64.0,
+            ores: OreSettings::defaults(),
+            decoration: DecorationSettings::default(),
+        }
+    }
+}
+
+impl ImageSettings {
+    /// The image has to open, and the scale and blend distance are divided by
+    pub fn validate(&self) -> Result<(), String> {
+        if self.scale <= 0.0 {
+            return Err(format!("scale has to be above 0, not {}", self.scale));
+        }
+        if self.blend <= 0.0 {
+            return Err(format!("blend has to be above 0, not {}", self.blend));
+        }
+        image::open(&self.path).map_err(|err| format!("failed to load heightmap {}: {}", self.path, err))?;
+        Ok(())
+    }
+}
+
+/// Terrain drawn in an image editor, pixel brightness is the height of the ground
+pub struct ImageGenerator {
+    /// brightness from 0 to 1, row by row
+    pixels: Vec<f32>,
+    width: i32,
+    height: i32,
+    noise: FastNoiseLite,
+    settings: ImageSettings,
+    sea_level: i32,
+    blocks: TerrainBlocks,
+    ores: Ores,
+}
+
+impl ImageGenerator {
+    /// `settings` have to pass `ImageSettings::validate`
+    pub fn new(registry: &BlockRegistry, seed: i32, settings: ImageSettings, sea_level: i32) -> ImageGenerator {
+        let image = image::open(&settings.path)
+            .unwrap_or_else(|err| panic!("failed to load heightmap {}: {}", settings.path, err))
+            .to_luma();
+        let pixels = image.pixels().map(|pixel| pixel.data[0] as f32 / 255.0).collect();
+
+        let mut noise = FastNoiseLite::with_seed(seed);
+        noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+        noise.set_frequency(Some(settings.noise_frequency));
+        noise.set_fractal_type(Some(FractalType::FBm));
+        noise.set_fractal_octaves(Some(4));
+
+        ImageGenerator {
+            pixels,
+            width: image.width() as i32,
+            height: image.height() as i32,
+            noise,
+            ores: Ores::new(registry, seed, &settings.ores),
+            blocks: TerrainBlocks {
+                surface: registry.get(&settings.surface),
+                subsurface: registry.get(&settings.subsurface),
+                filler: registry.get(&settings.filler),
+                water: registry.get("water"),
+            },
+            settings,
+            sea_level,
+        }
+    }
+
+    /// Pixel brightness, coordinates past the edge wrap around or are clamped to it
+    fn pixel(&self, x: i32, y: i32) -> f32 {
+        let (x, y) = match self.settings.edge {
+            ImageEdge::Tile => { (x.rem_euclid(self.width), y.rem_euclid(self.height)) }
+            ImageEdge::Noise => { (x.clamp(0, self.width - 1), y.clamp(0, self.height - 1)) }
+        };
+        self.pixels[(x + y * self.width) as usize]
+    }
+
+    /// Brightness at a world position, interpolated between pixels so scaled up images don't turn into stairs
+    pub fn brightness(&self, x: i32, z: i32) -> f32 {
+        let u = x as f32 / self.settings.scale + self.width as f32 / 2.0;
+        let v = z as f32 / self.settings.scale + self.height as f32 / 2.0;
+        let (px, py) = (u.floor() as i32, v.floor() as i32);
+        let (tx, ty) = (u - px as f32, v - py as f32);
+        let top = self.pixel(px, py) * (1.0 - tx) + self.pixel(px + 1, py) * tx;
+        let bottom = self.pixel(px, py + 1) * (1.0 - tx) + self.pixel(px + 1, py + 1) * tx;
+        let brightness = top * (1.0 - ty) + bottom * ty;
+        if self.settings.edge == ImageEdge::Tile {
+            return brightness;
+        }
+
+        // distance outside of the image in blocks
+        let outside_x = (-u).max(u - (self.width - 1) as f32).max(0.0);
+        let outside_y = (-v).max(v - (self.height - 1) as f32).max(0.0);
+        let distance = (outside_x * outside_x + outside_y * outside_y).sqrt() * self.settings.scale;
+        if distance <= 0.0 {
+            return brightness;
+        }
+        let noise = (self.noise.get_noise_2d(x as f32, z as f32) + 1.0) / 2.0;
+        let weight = (distance / self.settings.blend).min(1.0);
+        brightness + (noise - brightness) * weight
+    }
+
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        self.settings.offset + (self.brightness(x, z) * self.settings.height) as i32
+    }
+}
+
+impl TerrainGenerator for ImageGenerator {
+    fn generate(&self, position: ChunkPosition) -> Chunk {
+        let mut chunk = empty_chunk();
+        for x in 0..CS_I32 {
+            for z in 0..CS_I32 {
+                let height = self.height_at(position.x * CS_I32 + x, position.z * CS_I32 + z);
+                fill_column(&mut chunk, position, x, z, height, self.sea_level, &self.blocks);
+            }
+        }
+        self.ores.place(position, &mut chunk);
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::generator::heightmap_image::{ImageEdge, ImageGenerator, ImageSettings};
+    use crate::world::generator::terrain::GeneratorSettings;
+
+    fn generator(edge: ImageEdge) -> ImageGenerator {
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        ImageGenerator::new(&registry, 1234, ImageSettings { edge, ..ImageSettings::default() }, 14)
+    }
+
+    #[test]
+    fn edges_tile_or_fall_back_to_noise() {
+        let tiled = generator(ImageEdge::Tile);
+        // the example image is 128 pixels wide at 4 blocks per pixel
+        assert_eq!(tiled.height_at(10, 20), tiled.height_at(10 + 512, 20 - 512));
+
+        let noise = generator(ImageEdge::Noise);
+        assert_eq!(noise.height_at(10, 20), tiled.height_at(10, 20));
+        assert_ne!(noise.height_at(10 + 512, 20 - 512), tiled.height_at(10 + 512, 20 - 512));
+    }
+
+    #[test]
+    fn settings_that_cannot_generate_are_rejected() {
+        assert!(GeneratorSettings::parse("Image()").is_ok());
+        assert!(GeneratorSettings::parse("Image(path: \"resources/heightmaps/missing.png\")").is_err());
+        assert!(GeneratorSettings::parse("Image(scale: 0.0)").is_err());
+        assert!(GeneratorSettings::parse("Image(blend: 0.0)").is_err());
+    }
+}
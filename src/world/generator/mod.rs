@@ -0,0 +1,12 @@
+pub mod terrain;
+pub mod superflat;
+pub mod heightmap;
+pub mod fractal;
+pub mod heightmap_image;
+pub mod caves;
+pub mod biome;
+pub mod decoration;
+pub mod ores;
+pub mod erosion;
+#[cfg(test)]
+pub mod golden;
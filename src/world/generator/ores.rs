@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use crate::world::block::block::{Block, BlockType};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{local_index, Chunk, ChunkPosition, CS_I32};
+use crate::world::tick;
+
+/// A kind of ore vein
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OreSettings {
+    /// name of the ore block
+    pub block: String,
+    /// name of the block the ore replaces, anything else is left alone
+    pub host: String,
+    /// number of blocks a vein has at most, no more than a chunk is wide
+    pub size: u32,
+    /// veins per chunk
+    pub frequency: u32,
+    /// veins start at a y from `min_y` up to `max_y`, but can reach a bit outside of it
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+impl OreSettings {
+    fn new(block: &str, size: u32, frequency: u32, min_y: i32, max_y: i32) -> OreSettings {
+        OreSettings { block: String::from(block), host: String::from("cobblestone"), size, frequency, min_y, max_y }
+    }
+
+    pub fn defaults() -> Vec<OreSettings> {
+        vec![
+            OreSettings::new("coal_ore", 14, 24, -128, 128),
+            OreSettings::new("iron_ore", 8, 12, -128, 48),
+        ]
+    }
+
+    /// Veins are only looked for in the chunks right next to the one being generated,
+    /// a longer vein could reach further than that and would get cut off
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size > CS_I32 as u32 {
+            return Err(format!("{} veins can be at most {} blocks, not {}", self.block, CS_I32, self.size));
+        }
+        Ok(())
+    }
+}
+
+/// Places ore veins in the terrain. Veins wander up to their size away from where they start, which can be in a
+/// neighbouring chunk, so every chunk looks at the veins of its neighbours too and keeps the blocks that land inside it
+pub struct Ores {
+    seed: i64,
+    /// ore block, host block and settings
+    ores: Vec<(BlockType, BlockType, OreSettings)>,
+}
+
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+impl Ores {
+    pub fn new(registry: &BlockRegistry, seed: i32, settings: &[OreSettings]) -> Ores {
+        Ores {
+            seed: seed as i64,
+            ores: settings.iter()
+                .map(|ore| (registry.get(&ore.block), registry.get(&ore.host), ore.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn place(&self, position: ChunkPosition, chunk: &mut Chunk) {
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let origin_chunk = ChunkPosition { x: position.x + dx, y: position.y + dy, z: position.z + dz };
+                    self.place_veins_of(origin_chunk, position, chunk);
+                }
+            }
+        }
+    }
+
+    /// Places the blocks of the veins starting in `origin_chunk` that are inside `position`
+    fn place_veins_of(&self, origin_chunk: ChunkPosition, position: ChunkPosition, chunk: &mut Chunk) {
+        for (index, (ore, host, settings)) in self.ores.iter().enumerate() {
+            for vein in 0..settings.frequency {
+                let random = |step: i64| {
+                    tick::hash(&[self.seed, origin_chunk.x as i64, origin_chunk.y as i64, origin_chunk.z as i64, index as i64, vein as i64, step])
+                };
+                let start = random(-1);
+                let mut block = [
+                    origin_chunk.x * CS_I32 + (start % CS_I32 as u64) as i32,
+                    origin_chunk.y * CS_I32 + ((start >> 16) % CS_I32 as u64) as i32,
+                    origin_chunk.z * CS_I32 + ((start >> 32) % CS_I32 as u64) as i32,
+                ];
+                if block[1] < settings.min_y || block[1] >= settings.max_y {
+                    continue;
+                }
+
+                // a random walk, so veins are irregular blobs
+                for step in 0..settings.size {
+                    if ChunkPosition::containing(block[0], block[1], block[2]) == position {
+                        let target = &mut chunk.blocks[local_index(block[0], block[1], block[2])];
+                        if target.block_type == *host {
+                            *target = Block::new(*ore);
+                        }
+                    }
+                    let direction = DIRECTIONS[(random(step as i64) % DIRECTIONS.len() as u64) as usize];
+                    block = [block[0] + direction[0], block[1] + direction[1], block[2] + direction[2]];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::ChunkPosition;
+    use crate::world::generator::terrain::{GeneratorSettings, GeneratorType};
+
+    /// Ore blocks in the deep chunk of seed 8008135
+    const COAL: usize = 231;
+    const IRON: usize = 66;
+
+    fn count(seed: i32, position: ChunkPosition, name: &str) -> usize {
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        let generator = GeneratorType::Fractal.default_settings().create(&registry, seed, 14);
+        let block_type = registry.get(name);
+        generator.generate(position).blocks.iter().filter(|block| block.block_type == block_type).count()
+    }
+
+    #[test]
+    fn ore_placement_is_deterministic() {
+        let deep = ChunkPosition { x: 0, y: -3, z: 0 };
+        assert_eq!(count(8008135, deep, "coal_ore"), COAL);
+        assert_eq!(count(8008135, deep, "iron_ore"), IRON);
+        assert_ne!(count(42, deep, "coal_ore"), COAL);
+
+        // no iron that high up
+        assert_eq!(count(8008135, ChunkPosition { x: 0, y: 3, z: 0 }, "iron_ore"), 0);
+    }
+
+    #[test]
+    fn veins_longer_than_a_chunk_are_rejected() {
+        assert!(GeneratorSettings::parse("Fractal(ores: [(block: \"coal_ore\", host: \"cobblestone\", size: 32, frequency: 1, min_y: 0, max_y: 1)])").is_ok());
+        assert!(GeneratorSettings::parse("Fractal(ores: [(block: \"coal_ore\", host: \"cobblestone\", size: 33, frequency: 1, min_y: 0, max_y: 1)])").is_err());
+    }
+}
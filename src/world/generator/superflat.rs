@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use crate::world::block::block::{Block, BlockType};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{ChunkPosition, Chunk, CS_I32};
+use crate::world::generator::decoration::DecorationSettings;
+use crate::world::generator::terrain::{empty_chunk, TerrainGenerator};
+
+/// Layers of a superflat world, from y 0 upwards
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SuperflatSettings {
+    /// block name and how many blocks thick the layer is
+    pub layers: Vec<(String, u32)>,
+    pub decoration: DecorationSettings,
+}
+
+impl Default for SuperflatSettings {
+    fn default() -> SuperflatSettings {
+        SuperflatSettings {
+            layers: vec![(String::from("dirt"), 3), (String::from("grass"), 1)],
+            decoration: DecorationSettings::default(),
+        }
+    }
+}
+
+/// The same stack of layers everywhere, nothing below y 0 or above the last layer
+pub struct SuperflatGenerator {
+    /// block of every y from 0 up
+    column: Vec<BlockType>,
+}
+
+impl SuperflatGenerator {
+    pub fn new(registry: &BlockRegistry, settings: &SuperflatSettings) -> SuperflatGenerator {
+        let column = settings.layers.iter()
+            .flat_map(|(name, thickness)| std::iter::repeat_n(registry.get(name), *thickness as usize))
+            .collect();
+        SuperflatGenerator { column }
+    }
+}
+
+impl TerrainGenerator for SuperflatGenerator {
+    fn generate(&self, position: ChunkPosition) -> Chunk {
+        let mut chunk = empty_chunk();
+        for local_y in 0..CS_I32 {
+            let y = position.y * CS_I32 + local_y;
+            let Some(block_type) = usize::try_from(y).ok().and_then(|y| self.column.get(y)) else { continue };
+            for z in 0..CS_I32 {
+                for x in 0..CS_I32 {
+                    chunk.blocks[(x + local_y * CS_I32 + z * CS_I32 * CS_I32) as usize] = Block::new(*block_type);
+                }
+            }
+        }
+        chunk
+    }
+}
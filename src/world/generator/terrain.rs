@@ -0,0 +1,282 @@
+use fastnoise_lite::NoiseType;
+use ron::extensions::Extensions;
+use ron::Options;
+use serde::{Deserialize, Serialize};
+use crate::world::block::block::{Block, BlockType};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{Chunk, ChunkPosition, CS, CS_I32};
+use crate::world::generator::biome::{Biome, BiomeMap};
+use crate::world::generator::decoration::Decorator;
+use crate::world::generator::fractal::{FractalGenerator, FractalSettings};
+use crate::world::generator::heightmap_image::{ImageGenerator, ImageSettings};
+use crate::world::generator::heightmap::{HeightmapGenerator, HeightmapSettings};
+use crate::world::generator::superflat::{SuperflatGenerator, SuperflatSettings};
+
+/// Creates the blocks of a chunk. Chunks can be generated in any order and at any y,
+/// the same position always gives the same chunk
+pub trait TerrainGenerator {
+    fn generate(&self, position: ChunkPosition) -> Chunk;
+}
+
+/// Which generator a world uses
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GeneratorType {
+    Superflat,
+    /// a single layer of Perlin noise
+    Heightmap,
+    /// several octaves of noise added together, with 3D noise for overhangs and caves
+    Fractal,
+    /// heights read from a grayscale image
+    Image,
+}
+
+impl GeneratorType {
+    pub const ALL: [GeneratorType; 4] = [
+        GeneratorType::Superflat,
+        GeneratorType::Heightmap,
+        GeneratorType::Fractal,
+        GeneratorType::Image,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneratorType::Superflat => { "superflat" }
+            GeneratorType::Heightmap => { "heightmap" }
+            GeneratorType::Fractal => { "fractal" }
+            GeneratorType::Image => { "image" }
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GeneratorType> {
+        GeneratorType::ALL.into_iter().find(|it| it.name() == name)
+    }
+
+    pub fn default_settings(&self) -> GeneratorSettings {
+        match self {
+            GeneratorType::Superflat => { GeneratorSettings::Superflat(SuperflatSettings::default()) }
+            GeneratorType::Heightmap => { GeneratorSettings::Heightmap(HeightmapSettings::default()) }
+            GeneratorType::Fractal => { GeneratorSettings::Fractal(FractalSettings::default()) }
+            GeneratorType::Image => { GeneratorSettings::Image(ImageSettings::default()) }
+        }
+    }
+}
+
+/// Noise of `fastnoise_lite` the generators can be set to use
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum NoiseKind {
+    OpenSimplex2,
+    Perlin,
+    Value,
+    ValueCubic,
+}
+
+impl NoiseKind {
+    pub fn noise_type(&self) -> NoiseType {
+        match self {
+            NoiseKind::OpenSimplex2 => { NoiseType::OpenSimplex2 }
+            NoiseKind::Perlin => { NoiseType::Perlin }
+            NoiseKind::Value => { NoiseType::Value }
+            NoiseKind::ValueCubic => { NoiseType::ValueCubic }
+        }
+    }
+}
+
+/// A generator together with its parameters
+#[derive(Clone, Serialize, Deserialize)]
+pub enum GeneratorSettings {
+    Superflat(SuperflatSettings),
+    Heightmap(HeightmapSettings),
+    Fractal(FractalSettings),
+    Image(ImageSettings),
+}
+
+impl GeneratorSettings {
+    /// Either the name of a generator for its default settings, or settings written in RON
+    /// like `Fractal(octaves: 6, amplitude: 24.0)`, missing fields keep their default
+    pub fn parse(text: &str) -> Result<GeneratorSettings, String> {
+        if let Some(generator_type) = GeneratorType::from_name(text) {
+            return Ok(generator_type.default_settings());
+        }
+        let settings: GeneratorSettings = ron_options()
+            .from_str(text)
+            .map_err(|err| format!("invalid generator settings {}: {}", text, err))?;
+        settings.validate().map_err(|err| format!("invalid generator settings {}: {}", text, err))?;
+        Ok(settings)
+    }
+
+    /// Checks what can be written in RON but can't be generated
+    pub fn validate(&self) -> Result<(), String> {
+        let ores = match self {
+            GeneratorSettings::Superflat(_) => { &[][..] }
+            GeneratorSettings::Heightmap(settings) => { &settings.ores[..] }
+            GeneratorSettings::Fractal(settings) => { &settings.ores[..] }
+            GeneratorSettings::Image(settings) => {
+                settings.validate()?;
+                &settings.ores[..]
+            }
+        };
+        ores.iter().try_for_each(|ore| ore.validate())
+    }
+
+    pub fn generator_type(&self) -> GeneratorType {
+        match self {
+            GeneratorSettings::Superflat(_) => { GeneratorType::Superflat }
+            GeneratorSettings::Heightmap(_) => { GeneratorType::Heightmap }
+            GeneratorSettings::Fractal(_) => { GeneratorType::Fractal }
+            GeneratorSettings::Image(_) => { GeneratorType::Image }
+        }
+    }
+
+    /// Biomes the generator picks for each column, `World::biome_at` answers with these
+    pub fn biome_map(&self, seed: i32) -> Option<BiomeMap> {
+        match self {
+            GeneratorSettings::Fractal(settings) if settings.biomes.enabled => { Some(BiomeMap::new(seed, &settings.biomes)) }
+            _ => { None }
+        }
+    }
+
+    /// Trees and other features placed on top of the generated terrain
+    pub fn decorator(&self, registry: &BlockRegistry, seed: i32) -> Decorator {
+        let settings = match self {
+            GeneratorSettings::Superflat(settings) => { &settings.decoration }
+            GeneratorSettings::Heightmap(settings) => { &settings.decoration }
+            GeneratorSettings::Fractal(settings) => { &settings.decoration }
+            GeneratorSettings::Image(settings) => { &settings.decoration }
+        };
+        Decorator::new(registry, seed, settings)
+    }
+
+    pub fn create(&self, registry: &BlockRegistry, seed: i32, sea_level: i32) -> Box<dyn TerrainGenerator> {
+        match self {
+            GeneratorSettings::Superflat(settings) => { Box::new(SuperflatGenerator::new(registry, settings)) }
+            GeneratorSettings::Heightmap(settings) => { Box::new(HeightmapGenerator::new(registry, seed, settings.clone(), sea_level)) }
+            GeneratorSettings::Fractal(settings) => { Box::new(FractalGenerator::new(registry, seed, settings.clone(), sea_level)) }
+            GeneratorSettings::Image(settings) => { Box::new(ImageGenerator::new(registry, seed, settings.clone(), sea_level)) }
+        }
+    }
+}
+
+/// RON the way settings are written, enum variants with settings in them don't need two sets of parentheses
+pub fn ron_options() -> Options {
+    Options::default().with_default_extension(Extensions::UNWRAP_VARIANT_NEWTYPES)
+}
+
+/// Blocks the heightmap generators build their terrain out of
+#[derive(Clone, Copy)]
+pub struct TerrainBlocks {
+    /// top block of the ground, under water the subsurface block takes its place
+    pub surface: BlockType,
+    /// the few blocks below the surface
+    pub subsurface: BlockType,
+    pub filler: BlockType,
+    pub water: BlockType,
+}
+
+impl TerrainBlocks {
+    /// Grass on dirt on cobblestone
+    pub fn from_registry(registry: &BlockRegistry) -> TerrainBlocks {
+        Biome::Plains.blocks(registry)
+    }
+
+    /// Block of the ground at `y`, `height` is where the column's ground ends and `exposed` is whether there is no ground above
+    pub fn ground(&self, y: i32, height: i32, exposed: bool, sea_level: i32) -> BlockType {
+        if exposed {
+            // grass doesn't grow under water
+            if y + 1 >= sea_level { self.surface } else { self.subsurface }
+        } else if y >= height - 1 - SUBSURFACE_DEPTH {
+            self.subsurface
+        } else {
+            self.filler
+        }
+    }
+}
+
+/// How many blocks below the surface are subsurface blocks
+pub const SUBSURFACE_DEPTH: i32 = 3;
+
+/// A chunk with nothing but air in it
+pub fn empty_chunk() -> Chunk {
+    Chunk::create(vec![Block::new(BlockType::AIR); CS * CS * CS])
+}
+
+/// Fills the part of a block column that is inside the chunk. Everything below `height` is ground,
+/// with the surface block on top, and the air up to `sea_level` is water
+pub fn fill_column(chunk: &mut Chunk, position: ChunkPosition, x: i32, z: i32, height: i32, sea_level: i32, blocks: &TerrainBlocks) {
+    for local_y in 0..CS_I32 {
+        let y = position.y * CS_I32 + local_y;
+        let block_type = if y < height {
+            blocks.ground(y, height, y == height - 1, sea_level)
+        } else if y < sea_level {
+            blocks.water
+        } else {
+            break;
+        };
+        chunk.blocks[(x + local_y * CS_I32 + z * CS_I32 * CS_I32) as usize] = Block::new(block_type);
+    }
+}
+
+/// Blocks between two samples of a `NoiseLattice`
+pub const LATTICE_STEP: i32 = 4;
+const LATTICE_SIZE: i32 = CS_I32 / LATTICE_STEP + 1;
+
+/// 3D noise sampled every few blocks across a chunk and interpolated in between,
+/// smooth noise looks the same that way and it's a lot cheaper than sampling every block
+pub struct NoiseLattice {
+    samples: Vec<f32>,
+}
+
+impl NoiseLattice {
+    /// `noise` gets world coordinates
+    pub fn sample(position: ChunkPosition, noise: impl Fn(f32, f32, f32) -> f32) -> NoiseLattice {
+        let mut samples = Vec::with_capacity((LATTICE_SIZE * LATTICE_SIZE * LATTICE_SIZE) as usize);
+        for z in 0..LATTICE_SIZE {
+            for y in 0..LATTICE_SIZE {
+                for x in 0..LATTICE_SIZE {
+                    samples.push(noise(
+                        (position.x * CS_I32 + x * LATTICE_STEP) as f32,
+                        (position.y * CS_I32 + y * LATTICE_STEP) as f32,
+                        (position.z * CS_I32 + z * LATTICE_STEP) as f32,
+                    ));
+                }
+            }
+        }
+        NoiseLattice { samples }
+    }
+
+    /// The samples of a single block column, interpolated in x and z. Local coordinates
+    pub fn column(&self, x: i32, z: i32) -> LatticeColumn {
+        let ((cx, tx), (cz, tz)) = (lattice_cell(x), lattice_cell(z));
+        let sample = |x: i32, y: i32, z: i32| self.samples[(x + y * LATTICE_SIZE + z * LATTICE_SIZE * LATTICE_SIZE) as usize];
+        let mut samples = [0.0; LATTICE_SIZE as usize];
+        for (y, value) in samples.iter_mut().enumerate() {
+            let y = y as i32;
+            let near = lerp(sample(cx, y, cz), sample(cx + 1, y, cz), tx);
+            let far = lerp(sample(cx, y, cz + 1), sample(cx + 1, y, cz + 1), tx);
+            *value = lerp(near, far, tz);
+        }
+        LatticeColumn { samples }
+    }
+}
+
+/// A column of a `NoiseLattice`
+pub struct LatticeColumn {
+    samples: [f32; LATTICE_SIZE as usize],
+}
+
+impl LatticeColumn {
+    /// From 0 up to and including `CS` so the block above the chunk can be looked at too
+    pub fn get(&self, y: i32) -> f32 {
+        let (cell, t) = lattice_cell(y);
+        lerp(self.samples[cell as usize], self.samples[cell as usize + 1], t)
+    }
+}
+
+/// Lower sample and how far between it and the next one a local coordinate is
+fn lattice_cell(v: i32) -> (i32, f32) {
+    let cell = (v / LATTICE_STEP).min(LATTICE_SIZE - 2);
+    (cell, (v - cell * LATTICE_STEP) as f32 / LATTICE_STEP as f32)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
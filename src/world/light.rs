@@ -0,0 +1,453 @@
+use std::collections::{HashSet, VecDeque};
+use crate::world::chunk::chunk::{local_index, ChunkPosition, CS, CS_I32};
+use crate::world::world::World;
+
+pub const MAX_LIGHT: u8 = 15;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LightChannel {
+    /// light coming from the sky, passes straight down without getting weaker
+    Sky,
+    /// light coming from emissive blocks, every colour spreads on its own
+    Red,
+    Green,
+    Blue,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 4] = [LightChannel::Sky, LightChannel::Red, LightChannel::Green, LightChannel::Blue];
+
+    /// Bit offset of the channel in a packed light value, matches the unpacking in shader.vert
+    pub fn shift(&self) -> u16 {
+        match self {
+            LightChannel::Red => { 0 }
+            LightChannel::Green => { 4 }
+            LightChannel::Blue => { 8 }
+            LightChannel::Sky => { 12 }
+        }
+    }
+
+    /// Level a block with the given emission colour gives off in this channel
+    fn emission(&self, color: [u8; 3]) -> u8 {
+        match self {
+            LightChannel::Sky => { 0 }
+            LightChannel::Red => { color[0] }
+            LightChannel::Green => { color[1] }
+            LightChannel::Blue => { color[2] }
+        }.min(MAX_LIGHT)
+    }
+}
+
+/// Light levels of every block in a chunk, indexed the same as `Chunk::blocks`.
+/// Each channel takes 4 bits (red, green, blue, sky from the lowest bits up)
+pub struct LightStorage {
+    levels: Vec<u16>,
+}
+
+impl LightStorage {
+    pub fn new() -> LightStorage {
+        LightStorage {
+            levels: vec![0; CS * CS * CS],
+        }
+    }
+
+    pub fn get(&self, index: usize, channel: LightChannel) -> u8 {
+        ((self.levels[index] >> channel.shift()) & 0xF) as u8
+    }
+
+    pub fn set(&mut self, index: usize, channel: LightChannel, level: u8) {
+        let shift = channel.shift();
+        self.levels[index] = (self.levels[index] & !(0xF << shift)) | ((level as u16) << shift);
+    }
+
+    /// Every channel of a block at once
+    pub fn packed(&self, index: usize) -> u16 {
+        self.levels[index]
+    }
+}
+
+/// Offsets to the neighbours of a block, in the same order as the mesh faces
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [0, 1, 0],
+    [0, -1, 0],
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+const DOWN: usize = 1;
+
+type BlockPos = [i32; 3];
+
+/// Pending flood fill work, every chunk that had its light changed gets marked dirty once the queue is flushed
+struct LightQueue {
+    add: VecDeque<BlockPos>,
+    remove: VecDeque<(BlockPos, u8)>,
+    touched: HashSet<ChunkPosition>,
+    last_touched: Option<ChunkPosition>,
+}
+
+impl LightQueue {
+    fn new() -> LightQueue {
+        LightQueue {
+            add: VecDeque::new(),
+            remove: VecDeque::new(),
+            touched: HashSet::new(),
+            last_touched: None,
+        }
+    }
+}
+
+/// Lights a chunk that was just added to the world, pulling in light from neighbouring chunks
+/// and spreading its own light into them
+pub fn light_chunk(world: &mut World, position: ChunkPosition) {
+    let base = [position.x * CS_I32, position.y * CS_I32, position.z * CS_I32];
+
+    // looked up once, every colour channel seeds from the same emitters
+    let chunk = &world.chunks[&position];
+    let emitters: Vec<(usize, [u8; 3])> = chunk.blocks.iter()
+        .enumerate()
+        .map(|(index, block)| (index, world.registry.properties(block.block_type).light_emission))
+        .filter(|(_, color)| *color != [0; 3])
+        .collect();
+
+    for channel in LightChannel::ALL {
+        let mut queue = LightQueue::new();
+        queue.touched.insert(position);
+
+        match channel {
+            LightChannel::Sky => seed_sky_light(world, position, base, &mut queue),
+            _ => seed_block_light(world, position, base, channel, &emitters, &mut queue),
+        }
+
+        // light stored at the borders of loaded neighbours flows into this chunk,
+        // only where it is brighter than what this chunk already has
+        let chunk = &world.chunks[&position];
+        for (direction, offset) in DIRECTIONS.iter().enumerate() {
+            let neighbour_position = ChunkPosition { x: position.x + offset[0], y: position.y + offset[1], z: position.z + offset[2] };
+            let Some(neighbour) = world.chunks.get(&neighbour_position) else { continue };
+
+            for u in 0..CS_I32 {
+                for v in 0..CS_I32 {
+                    let inside = border_pos(base, *offset, u, v);
+                    let outside = offset_pos(inside, *offset);
+                    let level = neighbour.light.get(local_index(outside[0], outside[1], outside[2]), channel);
+                    let current = chunk.light.get(local_index(inside[0], inside[1], inside[2]), channel);
+
+                    // light coming from above travels downwards, the opposite of the offset direction
+                    let falls_in = channel == LightChannel::Sky && direction == 0 && level == MAX_LIGHT;
+                    if level > current + 1 || (falls_in && current < MAX_LIGHT) {
+                        queue.add.push_back(outside);
+                    }
+                }
+            }
+        }
+
+        flush(world, channel, &mut queue);
+    }
+}
+
+/// Block on the side of a chunk facing `offset`, `u` and `v` run along the other two axes
+#[inline]
+fn border_pos(base: BlockPos, offset: [i32; 3], u: i32, v: i32) -> BlockPos {
+    let axis = offset.iter().position(|it| *it != 0).unwrap();
+    let mut pos = base;
+    pos[axis] += if offset[axis] > 0 { CS_I32 - 1 } else { 0 };
+    pos[(axis + 1) % 3] += u;
+    pos[(axis + 2) % 3] += v;
+    pos
+}
+
+/// Updates light around a block that was changed, `set_block` calls this after the heightmap got updated
+pub fn update_light(world: &mut World, pos: BlockPos) {
+    let opaque = is_opaque(world, pos);
+
+    for channel in LightChannel::ALL {
+        let mut queue = LightQueue::new();
+
+        let old_level = light_at(world, pos, channel).unwrap_or(0);
+        if old_level > 0 {
+            set_light(world, pos, channel, 0, &mut queue);
+            queue.remove.push_back((pos, old_level));
+        }
+
+        let source = match channel {
+            LightChannel::Sky => { if !opaque && pos[1] >= world.height_at(pos[0], pos[2]) { MAX_LIGHT } else { 0 } }
+            _ => { emission(world, pos, channel) }
+        };
+        if source > 0 {
+            set_light(world, pos, channel, source, &mut queue);
+            queue.add.push_back(pos);
+        }
+
+        if !opaque {
+            // neighbours spread their light back into the changed block
+            for offset in DIRECTIONS {
+                queue.add.push_back(offset_pos(pos, offset));
+            }
+        }
+
+        flush(world, channel, &mut queue);
+    }
+}
+
+/// Fills every column above the heightmap with full sky light. Only blocks next to a taller
+/// neighbouring column need to spread sideways, everything else is already at full light
+fn seed_sky_light(world: &mut World, position: ChunkPosition, base: BlockPos, queue: &mut LightQueue) {
+    let mut covered = Vec::new();
+
+    // heights of this chunk column with a one block border from the neighbouring columns
+    const PADDED: usize = CS + 2;
+    let mut heights = vec![i32::MIN; PADDED * PADDED];
+    for x in -1..=CS_I32 {
+        for z in -1..=CS_I32 {
+            let inside = (0..CS_I32).contains(&x) && (0..CS_I32).contains(&z);
+            if !inside && (x == -1 || x == CS_I32) && (z == -1 || z == CS_I32) {
+                continue; // corners don't touch any column of this chunk
+            }
+            let height = match (inside, world.heightmap(position.x, position.z)) {
+                (true, Some(heightmap)) => { heightmap[(x + z * CS_I32) as usize] }
+                _ => { world.height_at(base[0] + x, base[2] + z) }
+            };
+            heights[(x + 1) as usize + (z + 1) as usize * PADDED] = height;
+        }
+    }
+
+    let chunk = world.chunks.get_mut(&position).unwrap();
+    for x in 0..CS_I32 {
+        for z in 0..CS_I32 {
+            let (world_x, world_z) = (base[0] + x, base[2] + z);
+            let padded = (x + 1) as usize + (z + 1) as usize * PADDED;
+            let height = heights[padded];
+
+            let neighbour_height = heights[padded + 1]
+                .max(heights[padded - 1])
+                .max(heights[padded + PADDED])
+                .max(heights[padded - PADDED]);
+
+            for y in height.max(base[1])..base[1] + CS_I32 {
+                chunk.light.set(local_index(world_x, y, world_z), LightChannel::Sky, MAX_LIGHT);
+                if y < neighbour_height {
+                    queue.add.push_back([world_x, y, world_z]);
+                }
+            }
+
+            if height > base[1] {
+                covered.push([world_x, base[1] - 1, world_z]);
+            }
+        }
+    }
+
+    // the chunk below was open to the sky, but this one covers it now
+    for below in covered {
+        if light_at(world, below, LightChannel::Sky) == Some(MAX_LIGHT) {
+            set_light(world, below, LightChannel::Sky, 0, queue);
+            queue.remove.push_back((below, MAX_LIGHT));
+        }
+    }
+}
+
+fn seed_block_light(world: &mut World, position: ChunkPosition, base: BlockPos, channel: LightChannel, emitters: &[(usize, [u8; 3])], queue: &mut LightQueue) {
+    let chunk = world.chunks.get_mut(&position).unwrap();
+
+    for (index, color) in emitters {
+        let level = channel.emission(*color);
+        if level > 0 {
+            chunk.light.set(*index, channel, level);
+            let (x, y, z) = (index % CS, (index / CS) % CS, index / (CS * CS));
+            queue.add.push_back([base[0] + x as i32, base[1] + y as i32, base[2] + z as i32]);
+        }
+    }
+}
+
+/// Runs the removal flood fill followed by the spreading one, then marks every changed chunk dirty
+fn flush(world: &mut World, channel: LightChannel, queue: &mut LightQueue) {
+    remove_light(world, channel, queue);
+    spread_light(world, channel, queue);
+
+    for position in queue.touched.drain() {
+        if let Some(chunk) = world.chunks.get_mut(&position) {
+            chunk.dirty = true;
+        }
+    }
+}
+
+fn spread_light(world: &mut World, channel: LightChannel, queue: &mut LightQueue) {
+    while let Some(pos) = queue.add.pop_front() {
+        let Some(level) = light_at(world, pos, channel) else { continue };
+        if level <= 1 {
+            continue;
+        }
+
+        for (direction, offset) in DIRECTIONS.iter().enumerate() {
+            let next = offset_pos(pos, *offset);
+            let next_level = if channel == LightChannel::Sky && direction == DOWN && level == MAX_LIGHT {
+                MAX_LIGHT
+            } else {
+                level - 1
+            };
+
+            if transparent_light_at(world, next, channel).is_some_and(|current| current < next_level) {
+                set_light(world, next, channel, next_level, queue);
+                queue.add.push_back(next);
+            }
+        }
+    }
+}
+
+/// Darkens every block that got its light from a removed source. Blocks that are lit by
+/// something else are queued to spread their light back into the darkened area
+fn remove_light(world: &mut World, channel: LightChannel, queue: &mut LightQueue) {
+    while let Some((pos, level)) = queue.remove.pop_front() {
+        for (direction, offset) in DIRECTIONS.iter().enumerate() {
+            let next = offset_pos(pos, *offset);
+            let Some(current) = light_at(world, next, channel) else { continue };
+            if current == 0 {
+                continue;
+            }
+
+            let lit_by_removed = current < level
+                || (channel == LightChannel::Sky && direction == DOWN && level == MAX_LIGHT && current == MAX_LIGHT);
+
+            if lit_by_removed {
+                set_light(world, next, channel, 0, queue);
+                queue.remove.push_back((next, current));
+
+                if channel != LightChannel::Sky {
+                    let source = emission(world, next, channel);
+                    if source > 0 {
+                        set_light(world, next, channel, source, queue);
+                        queue.add.push_back(next);
+                    }
+                }
+            } else {
+                queue.add.push_back(next);
+            }
+        }
+    }
+}
+
+#[inline]
+fn offset_pos(pos: BlockPos, offset: [i32; 3]) -> BlockPos {
+    [pos[0] + offset[0], pos[1] + offset[1], pos[2] + offset[2]]
+}
+
+/// Light level of a block, `None` if its chunk isn't loaded
+#[inline]
+fn light_at(world: &World, pos: BlockPos, channel: LightChannel) -> Option<u8> {
+    world.chunks.get(&ChunkPosition::containing(pos[0], pos[1], pos[2]))
+        .map(|chunk| chunk.light.get(local_index(pos[0], pos[1], pos[2]), channel))
+}
+
+#[inline]
+fn set_light(world: &mut World, pos: BlockPos, channel: LightChannel, level: u8, queue: &mut LightQueue) {
+    let position = ChunkPosition::containing(pos[0], pos[1], pos[2]);
+    if let Some(chunk) = world.chunks.get_mut(&position) {
+        chunk.light.set(local_index(pos[0], pos[1], pos[2]), channel, level);
+        if queue.last_touched != Some(position) {
+            queue.touched.insert(position);
+            queue.last_touched = Some(position);
+        }
+    }
+}
+
+/// Light level of a block that light can pass through, `None` if it is opaque or its chunk isn't loaded
+#[inline]
+fn transparent_light_at(world: &World, pos: BlockPos, channel: LightChannel) -> Option<u8> {
+    let chunk = world.chunks.get(&ChunkPosition::containing(pos[0], pos[1], pos[2]))?;
+    let index = local_index(pos[0], pos[1], pos[2]);
+    if world.registry.transparency(chunk.blocks[index].block_type).is_opaque() {
+        return None;
+    }
+    Some(chunk.light.get(index, channel))
+}
+
+/// Unloaded blocks count as opaque so light never spreads into them
+#[inline]
+fn is_opaque(world: &World, pos: BlockPos) -> bool {
+    match world.get_loaded_block(pos[0], pos[1], pos[2]) {
+        None => { true }
+        Some(block_type) => { world.registry.transparency(block_type).is_opaque() }
+    }
+}
+
+#[inline]
+fn emission(world: &World, pos: BlockPos, channel: LightChannel) -> u8 {
+    world.get_loaded_block(pos[0], pos[1], pos[2])
+        .map_or(0, |block_type| channel.emission(world.registry.properties(block_type).light_emission))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::block::BlockType;
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::{ChunkPosition, CS_I32};
+    use crate::world::generator::terrain::empty_chunk;
+    use crate::world::light::{LightChannel, MAX_LIGHT};
+    use crate::world::settings::WorldSettings;
+    use crate::world::world::World;
+
+    /// Chunks with nothing but air in them, everything around them is unloaded
+    fn empty_world(positions: &[ChunkPosition]) -> World {
+        let mut world = World::new(BlockRegistry::load("resources/blocks.ron"), WorldSettings::default());
+        for position in positions {
+            world.add_chunk(*position, empty_chunk());
+        }
+        world
+    }
+
+    fn block_light(world: &World, x: i32, y: i32, z: i32) -> [u8; 3] {
+        [LightChannel::Red, LightChannel::Green, LightChannel::Blue].map(|channel| world.get_light(x, y, z, channel))
+    }
+
+    #[test]
+    fn block_light_spreads_across_chunks_and_is_removed_again() {
+        let mut world = empty_world(&[ChunkPosition { x: 0, y: 0, z: 0 }, ChunkPosition { x: 1, y: 0, z: 0 }]);
+        let glowstone = world.registry.get("glowstone");
+        world.set_block(CS_I32 - 1, 5, 5, glowstone);
+        assert_eq!(block_light(&world, CS_I32 - 1, 5, 5), [15, 13, 9]);
+        assert_eq!(block_light(&world, CS_I32, 5, 5), [14, 12, 8]);
+        assert_eq!(block_light(&world, CS_I32 + 5, 5, 5), [9, 7, 3]);
+
+        world.set_block(CS_I32 - 1, 5, 5, BlockType::AIR);
+        let neighbour = &world.chunks[&ChunkPosition { x: 1, y: 0, z: 0 }];
+        for channel in [LightChannel::Red, LightChannel::Green, LightChannel::Blue] {
+            assert!((0..CS_I32 * CS_I32 * CS_I32).all(|index| neighbour.light.get(index as usize, channel) == 0));
+        }
+    }
+
+    #[test]
+    fn covering_a_column_takes_its_sky_light_away() {
+        let mut world = empty_world(&[ChunkPosition { x: 0, y: 0, z: 0 }, ChunkPosition { x: 0, y: 1, z: 0 }]);
+        assert_eq!(world.get_light(5, 10, 5, LightChannel::Sky), MAX_LIGHT);
+
+        // the cover is in the chunk above, the column below it is only lit from the sides
+        let cobblestone = world.registry.get("cobblestone");
+        world.set_block(5, CS_I32 + 8, 5, cobblestone);
+        assert_eq!(world.get_light(5, CS_I32 + 7, 5, LightChannel::Sky), MAX_LIGHT - 1);
+        assert_eq!(world.get_light(5, 10, 5, LightChannel::Sky), MAX_LIGHT - 1);
+        assert_eq!(world.get_light(6, 10, 5, LightChannel::Sky), MAX_LIGHT);
+
+        world.set_block(5, CS_I32 + 8, 5, BlockType::AIR);
+        assert_eq!(world.get_light(5, CS_I32 + 7, 5, LightChannel::Sky), MAX_LIGHT);
+        assert_eq!(world.get_light(5, 10, 5, LightChannel::Sky), MAX_LIGHT);
+    }
+
+    #[test]
+    fn colour_channels_spread_on_their_own() {
+        let mut world = empty_world(&[ChunkPosition { x: 0, y: 0, z: 0 }]);
+        let (glowstone, lava) = (world.registry.get("glowstone"), world.registry.get("lava"));
+        world.set_block(10, 5, 5, glowstone);
+        world.set_block(20, 5, 5, lava);
+
+        // red comes from the lava, green and blue from the glowstone, each channel takes the brighter one
+        assert_eq!(block_light(&world, 16, 5, 5), [11, 7, 3]);
+        // blue of the glowstone runs out first
+        assert_eq!(block_light(&world, 1, 5, 5), [6, 4, 0]);
+        // sky light isn't touched by any of it
+        assert_eq!(world.get_light(16, 5, 5, LightChannel::Sky), MAX_LIGHT);
+
+        world.set_block(20, 5, 5, BlockType::AIR);
+        assert_eq!(block_light(&world, 16, 5, 5), [9, 7, 3]);
+    }
+}
@@ -1,3 +1,10 @@
 pub mod world;
 pub mod chunk;
-pub mod block;
\ No newline at end of file
+pub mod block;
+pub mod light;
+pub mod time;
+pub mod tick;
+pub mod fluid;
+pub mod generator;
+pub mod settings;
+pub mod streaming;
\ No newline at end of file
@@ -0,0 +1,129 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::generator::decoration::Decorator;
+use crate::world::generator::terrain::{ron_options, GeneratorSettings, GeneratorType, TerrainGenerator};
+
+/// Folder the settings of every world are saved in
+const WORLDS_PATH: &str = "worlds";
+pub const DEFAULT_SEA_LEVEL: i32 = 14;
+
+/// Everything the terrain of a world is generated from, the same settings always generate the same terrain
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorldSettings {
+    pub name: String,
+    pub seed: i32,
+    /// every block below this y that the terrain doesn't reach is water
+    pub sea_level: i32,
+    /// generator and its noise parameters
+    pub generator: GeneratorSettings,
+}
+
+impl Default for WorldSettings {
+    fn default() -> WorldSettings {
+        WorldSettings {
+            name: String::from("world"),
+            seed: 8008135,
+            sea_level: DEFAULT_SEA_LEVEL,
+            generator: GeneratorType::Fractal.default_settings(),
+        }
+    }
+}
+
+impl WorldSettings {
+    /// Settings of a new world with a random seed
+    pub fn new(name: &str, generator: GeneratorSettings) -> WorldSettings {
+        WorldSettings {
+            name: String::from(name),
+            seed: rand::random(),
+            sea_level: DEFAULT_SEA_LEVEL,
+            generator,
+        }
+    }
+
+    /// Where the settings of the world called `name` are saved
+    pub fn path(name: &str) -> PathBuf {
+        PathBuf::from(WORLDS_PATH).join(format!("{}.ron", name))
+    }
+
+    /// Names end up in a path, so they can't lead out of the worlds folder
+    pub fn check_name(name: &str) -> Result<(), String> {
+        if name.is_empty() || name.contains(['/', '\\', ':']) || name.contains("..") {
+            return Err(format!("invalid world name {:?}, it can't be empty or contain /, \\, : or ..", name));
+        }
+        Ok(())
+    }
+
+    /// `None` if there is no world called `name` yet, any other problem reading it is an error
+    pub fn load(name: &str) -> Option<Result<WorldSettings, String>> {
+        if let Err(err) = WorldSettings::check_name(name) {
+            return Some(Err(err));
+        }
+        let path = WorldSettings::path(name);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => { text }
+            Err(err) if err.kind() == ErrorKind::NotFound => { return None }
+            Err(err) => { return Some(Err(format!("failed to read {}: {}", path.display(), err))) }
+        };
+        Some(WorldSettings::parse(&text).map_err(|err| format!("invalid world settings in {}: {}", path.display(), err)))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        WorldSettings::check_name(&self.name)?;
+        let path = WorldSettings::path(&self.name);
+        fs::create_dir_all(WORLDS_PATH)
+            .and_then(|_| fs::write(&path, self.to_ron()))
+            .map_err(|err| format!("failed to save {}: {}", path.display(), err))
+    }
+
+    pub fn parse(text: &str) -> Result<WorldSettings, String> {
+        let settings: WorldSettings = ron_options().from_str(text).map_err(|err| err.to_string())?;
+        settings.generator.validate()?;
+        Ok(settings)
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron_options()
+            .to_string_pretty(self, PrettyConfig::new())
+            .unwrap_or_else(|err| panic!("failed to write world settings: {}", err))
+    }
+
+    pub fn create_generator(&self, registry: &BlockRegistry) -> Box<dyn TerrainGenerator> {
+        self.generator.create(registry, self.seed, self.sea_level)
+    }
+
+    pub fn create_decorator(&self, registry: &BlockRegistry) -> Decorator {
+        self.generator.decorator(registry, self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::ChunkPosition;
+    use crate::world::settings::WorldSettings;
+
+    #[test]
+    fn saved_settings_generate_the_same_terrain() {
+        let registry = BlockRegistry::load("resources/blocks.ron");
+        let settings = WorldSettings { seed: 1234, ..WorldSettings::default() };
+        let reloaded = WorldSettings::parse(&settings.to_ron()).unwrap();
+        assert_eq!(reloaded.to_ron(), settings.to_ron());
+
+        let position = ChunkPosition { x: 2, y: 1, z: -1 };
+        let chunk = settings.create_generator(&registry).generate(position);
+        assert!(chunk.blocks == reloaded.create_generator(&registry).generate(position).blocks);
+    }
+
+    #[test]
+    fn names_stay_inside_the_worlds_folder() {
+        assert!(WorldSettings::check_name("my world").is_ok());
+        for name in ["", "../x", "/tmp/x", "a\\b", "..", "c:x"] {
+            assert!(WorldSettings::check_name(name).is_err(), "{:?} was accepted", name);
+            assert!(WorldSettings::load(name).is_some_and(|settings| settings.is_err()));
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{HashMap, HashSet};
+use crate::world::block::block::{Block, BlockType};
+use crate::world::chunk::chunk::{local_index, Chunk, ChunkPosition};
+use crate::world::generator::decoration::{Decorator, FeatureBlock};
+use crate::world::generator::terrain::{empty_chunk, TerrainGenerator};
+use crate::world::world::World;
+
+/// Chunk y range of every column, y -128 up to 255 in blocks
+pub const MIN_CHUNK_Y: i32 = -4;
+pub const MAX_CHUNK_Y: i32 = 7;
+
+/// Chunk x and z of a column
+type Column = (i32, i32);
+
+/// Loads whole columns of chunks around a center as it moves, and unloads the ones that got too far away
+pub struct ChunkStreamer {
+    generator: Box<dyn TerrainGenerator>,
+    decorator: Decorator,
+    /// columns closer than this many chunks get loaded
+    pub distance: i32,
+    columns: HashSet<Column>,
+    /// feature blocks by the column they are in, with the column whose feature they belong to.
+    /// They are kept as long as that column is loaded, so they are there whenever their own column generates
+    features: HashMap<Column, Vec<(Column, FeatureBlock)>>,
+}
+
+impl ChunkStreamer {
+    pub fn new(generator: Box<dyn TerrainGenerator>, decorator: Decorator, distance: i32) -> ChunkStreamer {
+        ChunkStreamer {
+            generator,
+            decorator,
+            distance,
+            columns: HashSet::new(),
+            features: HashMap::new(),
+        }
+    }
+
+    /// Loads at most `budget` missing columns, closest first, and unloads every column that is more than a chunk
+    /// past the distance, so moving back and forth across the border doesn't load the same column again and again.
+    /// Returns the chunks that got unloaded, their meshes still have to be freed
+    pub fn update(&mut self, world: &mut World, center: ChunkPosition, budget: usize) -> Vec<Chunk> {
+        let distance_sq = |(x, z): (i32, i32)| (x - center.x).pow(2) + (z - center.z).pow(2);
+
+        let unload_distance = (self.distance + 1).pow(2);
+        let far: Vec<(i32, i32)> = self.columns.iter().copied().filter(|column| distance_sq(*column) > unload_distance).collect();
+        let mut unloaded = Vec::new();
+        for column in far {
+            self.columns.remove(&column);
+            unloaded.extend(world.remove_column(column.0, column.1));
+            // they come back when the column generates again
+            for blocks in self.features.values_mut() {
+                blocks.retain(|(source, _)| *source != column);
+            }
+        }
+        self.features.retain(|_, blocks| !blocks.is_empty());
+
+        let mut missing = Vec::new();
+        for x in center.x - self.distance..=center.x + self.distance {
+            for z in center.z - self.distance..=center.z + self.distance {
+                if distance_sq((x, z)) <= self.distance.pow(2) && !self.columns.contains(&(x, z)) {
+                    missing.push((x, z));
+                }
+            }
+        }
+        missing.sort_unstable_by_key(|column| (distance_sq(*column), column.0, column.1));
+        for column in missing.into_iter().take(budget) {
+            self.load_column(world, column);
+        }
+        unloaded
+    }
+
+    /// Generates and decorates every chunk of a column, chunks with nothing but air in them are left out
+    fn load_column(&mut self, world: &mut World, (x, z): (i32, i32)) {
+        let mut chunks: Vec<(ChunkPosition, Chunk)> = (MIN_CHUNK_Y..=MAX_CHUNK_Y)
+            .map(|y| {
+                let position = ChunkPosition { x, y, z };
+                (position, self.generator.generate(position))
+            })
+            .collect();
+
+        for feature in self.decorator.decorate(&chunks) {
+            let target = ChunkPosition::containing(feature.position[0], feature.position[1], feature.position[2]);
+            if (target.x, target.z) == (x, z) {
+                place_feature(&self.decorator, &mut chunks, feature);
+                continue;
+            }
+            self.features.entry((target.x, target.z)).or_default().push(((x, z), feature));
+            // already generated, so it won't pick it up by itself
+            if self.columns.contains(&(target.x, target.z)) {
+                place_loaded_feature(&self.decorator, world, target, feature);
+            }
+        }
+        for (_, feature) in self.features.get(&(x, z)).into_iter().flatten() {
+            place_feature(&self.decorator, &mut chunks, *feature);
+        }
+
+        // top down, so the heightmap already knows about the terrain above a chunk when it gets lit
+        for (position, chunk) in chunks.into_iter().rev() {
+            if chunk.blocks.iter().any(|block| block.block_type != BlockType::AIR) {
+                world.add_chunk(position, chunk);
+            }
+        }
+        self.columns.insert((x, z));
+    }
+}
+
+/// Places a feature block in a column that is being generated, `chunks` goes from the lowest chunk up
+fn place_feature(decorator: &Decorator, chunks: &mut [(ChunkPosition, Chunk)], feature: FeatureBlock) {
+    let [x, y, z] = feature.position;
+    let chunk_y = ChunkPosition::containing(x, y, z).y;
+    if !(MIN_CHUNK_Y..=MAX_CHUNK_Y).contains(&chunk_y) {
+        return;
+    }
+    let block = &mut chunks[(chunk_y - MIN_CHUNK_Y) as usize].1.blocks[local_index(x, y, z)];
+    if decorator.replaces(block.block_type, feature.block_type) {
+        *block = Block::new(feature.block_type);
+    }
+}
+
+/// Places a feature block in a column that is already in the world
+fn place_loaded_feature(decorator: &Decorator, world: &mut World, target: ChunkPosition, feature: FeatureBlock) {
+    if !(MIN_CHUNK_Y..=MAX_CHUNK_Y).contains(&target.y) {
+        return;
+    }
+    // chunks with nothing but air in them were left out
+    if !world.chunks.contains_key(&target) {
+        world.add_chunk(target, empty_chunk());
+    }
+    let [x, y, z] = feature.position;
+    if decorator.replaces(world.get_block(x, y, z), feature.block_type) {
+        world.set_block(x, y, z, feature.block_type);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::world::block::block::BlockType;
+    use crate::world::block::registry::BlockRegistry;
+    use crate::world::chunk::chunk::{ChunkPosition, CS_I32};
+    use crate::world::generator::terrain::{GeneratorSettings, GeneratorType};
+    use crate::world::settings::WorldSettings;
+    use crate::world::streaming::{ChunkStreamer, MAX_CHUNK_Y, MIN_CHUNK_Y};
+    use crate::world::world::World;
+
+    fn streamed(generator: GeneratorSettings, centers: &[ChunkPosition]) -> World {
+        let settings = WorldSettings { seed: 1234, generator, ..WorldSettings::default() };
+        let mut world = World::new(BlockRegistry::load("resources/blocks.ron"), settings);
+        let generator = world.settings.create_generator(&world.registry);
+        let mut streamer = ChunkStreamer::new(generator, world.settings.create_decorator(&world.registry), 3);
+        for center in centers {
+            streamer.update(&mut world, *center, usize::MAX);
+        }
+        world
+    }
+
+    /// Loads the same columns once straight away and once after loading some further away first,
+    /// the columns around the origin have to come out the same
+    fn assert_order_independent(generator: GeneratorSettings) -> (World, World) {
+        let origin = ChunkPosition { x: 0, y: 0, z: 0 };
+        let first = streamed(generator.clone(), &[origin]);
+        let second = streamed(generator, &[ChunkPosition { x: 3, y: 0, z: 3 }, origin]);
+        for x in -1..=1 {
+            for z in -1..=1 {
+                for y in MIN_CHUNK_Y..=MAX_CHUNK_Y {
+                    let position = ChunkPosition { x, y, z };
+                    let blocks = |world: &World| world.chunks.get(&position).map(|chunk| chunk.blocks.clone());
+                    assert!(blocks(&first) == blocks(&second), "chunk {:?} differs", position);
+                }
+            }
+        }
+        (first, second)
+    }
+
+    #[test]
+    fn features_do_not_depend_on_generation_order() {
+        let (world, _) = assert_order_independent(GeneratorType::Superflat.default_settings());
+        let log = world.registry.get("log");
+        assert!(world.chunks[&ChunkPosition { x: 0, y: 0, z: 0 }].blocks.iter().any(|block| block.block_type == log));
+    }
+
+    #[test]
+    fn unloading_a_column_remeshes_its_neighbours() {
+        let mut world = streamed(GeneratorType::Superflat.default_settings(), &[ChunkPosition { x: 0, y: 0, z: 0 }]);
+        for chunk in world.chunks.values_mut() {
+            chunk.dirty = false;
+        }
+        world.remove_column(1, 0);
+        assert!(world.chunks[&ChunkPosition { x: 0, y: 0, z: 0 }].dirty);
+        assert!(world.chunks[&ChunkPosition { x: 2, y: 0, z: 1 }].dirty);
+        assert!(!world.chunks[&ChunkPosition { x: -1, y: 0, z: 0 }].dirty);
+    }
+
+    #[test]
+    fn features_grow_on_the_surface_of_fractal_terrain() {
+        let (world, _) = assert_order_independent(GeneratorType::Fractal.default_settings());
+        let generator = world.settings.create_generator(&world.registry);
+        let mut terrain = HashMap::new();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                for y in MIN_CHUNK_Y..=MAX_CHUNK_Y {
+                    let position = ChunkPosition { x, y, z };
+                    terrain.insert(position, generator.generate(position));
+                }
+            }
+        }
+        let (log, leaves) = (world.registry.get("log"), world.registry.get("leaves"));
+
+        let mut crosses_chunks = false;
+        let mut trees = 0;
+        for x in -CS_I32..2 * CS_I32 {
+            for z in -CS_I32..2 * CS_I32 {
+                // the highest block the terrain has without any features
+                let surface = (MIN_CHUNK_Y..=MAX_CHUNK_Y).rev()
+                    .find_map(|chunk_y| {
+                        let position = ChunkPosition { x: x.div_euclid(CS_I32), y: chunk_y, z: z.div_euclid(CS_I32) };
+                        let chunk = &terrain[&position];
+                        (0..CS_I32).rev()
+                            .find(|y| chunk.blocks[(x.rem_euclid(CS_I32) + y * CS_I32 + z.rem_euclid(CS_I32) * CS_I32 * CS_I32) as usize].block_type != BlockType::AIR)
+                            .map(|y| chunk_y * CS_I32 + y)
+                    })
+                    .unwrap();
+                for y in MIN_CHUNK_Y * CS_I32..(MAX_CHUNK_Y + 1) * CS_I32 {
+                    let block_type = world.get_block(x, y, z);
+                    if block_type != log && block_type != leaves {
+                        continue;
+                    }
+                    assert!(y > surface, "feature block at {:?} is below the surface at {}", [x, y, z], surface);
+                    if block_type == log && y == surface + 1 {
+                        trees += 1;
+                    }
+                    if block_type == log && y.rem_euclid(CS_I32) == CS_I32 - 1 && world.get_block(x, y + 1, z) == log {
+                        crosses_chunks = true;
+                    }
+                }
+            }
+        }
+        assert!(trees > 0);
+        assert!(crosses_chunks, "no tree crosses a chunk border");
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::{BTreeMap, HashSet};
+
+type BlockPos = [i32; 3];
+
+/// Block positions waiting to be updated at a later tick. Updates due at the same tick run in the order
+/// they were scheduled, which keeps the simulation deterministic. A position is only ever waiting once
+pub struct ScheduledUpdates {
+    queue: BTreeMap<u64, Vec<BlockPos>>,
+    pending: HashSet<BlockPos>,
+}
+
+impl ScheduledUpdates {
+    pub fn new() -> ScheduledUpdates {
+        ScheduledUpdates {
+            queue: BTreeMap::new(),
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Does nothing if the position is already waiting for an update
+    pub fn schedule(&mut self, pos: BlockPos, tick: u64) {
+        if self.pending.insert(pos) {
+            self.queue.entry(tick).or_default().push(pos);
+        }
+    }
+
+    /// Removes and returns every update due at or before `tick`
+    pub fn take_due(&mut self, tick: u64) -> Vec<BlockPos> {
+        let later = self.queue.split_off(&(tick + 1));
+        let due: Vec<BlockPos> = std::mem::replace(&mut self.queue, later).into_values().flatten().collect();
+        for pos in &due {
+            self.pending.remove(pos);
+        }
+        due
+    }
+
+    /// Number of positions waiting for an update
+    pub fn count(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Deterministic pseudo random number from a few values, the same values always give the same number
+pub fn hash(values: &[i64]) -> u64 {
+    values.iter().fold(0x9E37_79B9_7F4A_7C15, |hash, value| mix(hash ^ *value as u64))
+}
+
+/// Finalizer of splitmix64, spreads every input bit over the whole output
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScheduledUpdates;
+
+    #[test]
+    fn due_updates_keep_their_order() {
+        let mut updates = ScheduledUpdates::new();
+        updates.schedule([3, 0, 0], 2);
+        updates.schedule([1, 0, 0], 1);
+        updates.schedule([2, 0, 0], 2);
+        updates.schedule([1, 0, 0], 2);
+        updates.schedule([4, 0, 0], 5);
+
+        assert_eq!(updates.take_due(0), Vec::<[i32; 3]>::new());
+        assert_eq!(updates.take_due(2), vec![[1, 0, 0], [3, 0, 0], [2, 0, 0]]);
+        assert_eq!(updates.count(), 1);
+        assert_eq!(updates.take_due(5), vec![[4, 0, 0]]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::f32::consts::TAU;
+use ultraviolet::{Lerp, Vec3};
+
+/// Seconds a full day takes at normal speed
+const DAY_LENGTH: f32 = 600.0;
+const MAX_SPEED: f32 = 256.0;
+
+pub const MIDNIGHT: f32 = 0.0;
+pub const SUNRISE: f32 = 0.25;
+pub const NOON: f32 = 0.5;
+pub const SUNSET: f32 = 0.75;
+
+const DAY_ZENITH: Vec3 = Vec3::new(0.25, 0.5, 0.95);
+const DAY_HORIZON: Vec3 = Vec3::new(0.7, 0.82, 0.97);
+const NIGHT_ZENITH: Vec3 = Vec3::new(0.005, 0.01, 0.04);
+const NIGHT_HORIZON: Vec3 = Vec3::new(0.03, 0.04, 0.09);
+const SUNSET_HORIZON: Vec3 = Vec3::new(0.95, 0.45, 0.2);
+
+/// Time of day clock, drives the sun, the moon, the sky colours and how bright sky light is
+pub struct WorldTime {
+    /// how far into the day it is, 0 is midnight and 0.5 is noon
+    pub time_of_day: f32,
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl WorldTime {
+    pub fn new() -> WorldTime {
+        WorldTime {
+            time_of_day: 0.35,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if !self.paused {
+            self.time_of_day = (self.time_of_day + delta_time * self.speed / DAY_LENGTH).rem_euclid(1.0);
+        }
+    }
+
+    pub fn set(&mut self, time_of_day: f32) {
+        self.time_of_day = time_of_day.rem_euclid(1.0);
+    }
+
+    pub fn speed_up(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_SPEED);
+    }
+
+    pub fn slow_down(&mut self) {
+        self.speed = (self.speed / 2.0).max(1.0);
+    }
+
+    /// Time as hours and minutes of a 24 hour clock
+    pub fn clock(&self) -> (u32, u32) {
+        let minutes = (self.time_of_day * 24.0 * 60.0) as u32;
+        (minutes / 60, minutes % 60)
+    }
+
+    /// Points towards the sun, it rises in +x, is straight up at noon and sets in -x
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time_of_day - SUNRISE) * TAU;
+        // slightly tilted so the sun doesn't pass exactly overhead
+        Vec3::new(angle.cos(), angle.sin(), 0.25).normalized()
+    }
+
+    pub fn moon_direction(&self) -> Vec3 {
+        -self.sun_direction()
+    }
+
+    /// Direction shadows get cast from, the sun during the day and the moon at night
+    pub fn light_direction(&self) -> Vec3 {
+        let sun = self.sun_direction();
+        if sun.y >= 0.0 { sun } else { -sun }
+    }
+
+    /// How strong shadows are, they fade out while the sun or moon is close to the horizon
+    /// so the switch between them isn't visible. Moonlight shadows are weaker
+    pub fn shadow_strength(&self) -> f32 {
+        smoothstep(0.0, 0.15, self.light_direction().y) * (0.4 + 0.6 * self.daylight())
+    }
+
+    /// 0 at night up to 1 at day, fades while the sun is close to the horizon
+    pub fn daylight(&self) -> f32 {
+        smoothstep(-0.2, 0.2, self.sun_direction().y)
+    }
+
+    /// How bright full sky light is, it never gets fully dark so the moon still lights the world
+    pub fn skylight_multiplier(&self) -> f32 {
+        0.15 + 0.85 * self.daylight()
+    }
+
+    /// Sky colour straight up
+    pub fn zenith_color(&self) -> Vec3 {
+        NIGHT_ZENITH.lerp(DAY_ZENITH, self.daylight())
+    }
+
+    /// Sky colour at the horizon, turns orange around sunrise and sunset
+    pub fn horizon_color(&self) -> Vec3 {
+        let horizon = NIGHT_HORIZON.lerp(DAY_HORIZON, self.daylight());
+        let sunset = 1.0 - smoothstep(0.0, 0.3, self.sun_direction().y.abs());
+        horizon.lerp(SUNSET_HORIZON, sunset * 0.6)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -1,73 +1,325 @@
 use std::collections::HashMap;
-use fastnoise_lite::FastNoiseLite;
-use crate::world::chunk::chunk::{Block, BlockType, Chunk, CS, ChunkPosition, CS_F32, CS_I32};
+use ultraviolet::Vec3;
+use crate::world::block::block::{Block, BlockType};
+use crate::world::block::registry::BlockRegistry;
+use crate::world::chunk::chunk::{local_index, Chunk, CS, ChunkPosition, CS_I32};
+use crate::world::fluid;
+use crate::world::generator::biome::{Biome, BiomeMap};
+use crate::world::light::{self, LightChannel};
+use crate::world::settings::WorldSettings;
+use crate::world::streaming::{MAX_CHUNK_Y, MIN_CHUNK_Y};
+use crate::world::tick::{self, ScheduledUpdates};
+use crate::world::time::WorldTime;
 
-const CHUNK_AMOUNT: i32 = 32;
-const SUPER_FLAT: bool = false;
+pub const TICKS_PER_SECOND: f32 = 20.0;
+/// if a frame took longer than this many ticks the world slows down instead of trying to catch up
+const MAX_TICKS_PER_FRAME: u32 = 10;
+/// random ticks pick blocks per cube of this size, a chunk has 8 of them
+const SECTION_SIZE: i32 = 16;
+const RANDOM_TICKS_PER_SECTION: u64 = 3;
+/// in chunks, only chunks this close to the simulation center get random ticks
+const SIMULATION_DISTANCE: i32 = 8;
+
+/// A block and its six neighbours
+const NEIGHBOURHOOD: [[i32; 3]; 7] = [
+    [0, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
 
 
 pub struct World {
     pub chunks: HashMap<ChunkPosition, Chunk>,
+    pub registry: BlockRegistry,
+    pub time: WorldTime,
+    /// what the terrain is generated from
+    pub settings: WorldSettings,
+    /// per chunk column (x, z), the y above the highest opaque block of every block column
+    heightmaps: HashMap<(i32, i32), Vec<i32>>,
+    /// number of ticks since the world was created
+    pub tick: u64,
+    /// time that passed since the last tick, in seconds
+    tick_accumulator: f32,
+    scheduled_updates: ScheduledUpdates,
+    /// chunk random ticks are centered around, usually the one the camera is in
+    pub simulation_center: ChunkPosition,
+    /// biomes the generator picks, if it has any
+    biomes: Option<BiomeMap>,
 }
 
 impl World {
-    pub fn new() -> Self {
-        let mut world = Self {
+    pub fn new(registry: BlockRegistry, settings: WorldSettings) -> Self {
+        Self {
+            biomes: settings.generator.biome_map(settings.seed),
             chunks: HashMap::new(),
-        };
-        world
+            registry,
+            time: WorldTime::new(),
+            settings,
+            heightmaps: HashMap::new(),
+            tick: 0,
+            tick_accumulator: 0.0,
+            scheduled_updates: ScheduledUpdates::new(),
+            simulation_center: ChunkPosition { x: 0, y: 0, z: 0 },
+        }
     }
 
-    fn add_chunk(&mut self, chunk_position: ChunkPosition, chunk: Chunk) {
+    pub fn add_chunk(&mut self, chunk_position: ChunkPosition, chunk: Chunk) {
+        let heightmap = self.heightmaps
+            .entry((chunk_position.x, chunk_position.z))
+            .or_insert_with(|| vec![i32::MIN; CS * CS]);
+
+        for x in 0..CS {
+            for z in 0..CS {
+                for y in (0..CS).rev() {
+                    let block_type = chunk.blocks[x + y * CS + z * CS * CS].block_type;
+                    if self.registry.transparency(block_type).is_opaque() {
+                        let height = &mut heightmap[x + z * CS];
+                        *height = (*height).max(chunk_position.y * CS_I32 + y as i32 + 1);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // meshes of the neighbours can cull and light their border against this chunk now
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbour = ChunkPosition { x: chunk_position.x + dx, y: chunk_position.y + dy, z: chunk_position.z + dz };
+                    if let Some(chunk) = self.chunks.get_mut(&neighbour) {
+                        chunk.dirty = true;
+                    }
+                }
+            }
+        }
+
         self.chunks.insert(chunk_position, chunk);
+        light::light_chunk(self, chunk_position);
     }
-}
 
-// todo: multi threaded
-pub fn make_example_chunks(world: &mut World, noise: &FastNoiseLite) {
-    let ca = CHUNK_AMOUNT * 2;
-    for x in -ca..ca {
-        for z in -ca..ca {
-            let position = ChunkPosition { x, y: 0, z };
-            world.add_chunk(
-                position, generate_chunk_noise(x * CS as i32, z * CS as i32, noise)
-            );
+    /// Removes every chunk of a column and its heightmap, returns the removed chunks
+    pub fn remove_column(&mut self, x: i32, z: i32) -> Vec<Chunk> {
+        self.heightmaps.remove(&(x, z));
+
+        // meshes of the neighbouring columns were culled and lit against this column
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                for y in MIN_CHUNK_Y..=MAX_CHUNK_Y {
+                    if let Some(chunk) = self.chunks.get_mut(&ChunkPosition { x: x + dx, y, z: z + dz }) {
+                        chunk.dirty = true;
+                    }
+                }
+            }
         }
+
+        (MIN_CHUNK_Y..=MAX_CHUNK_Y)
+            .filter_map(|y| self.chunks.remove(&ChunkPosition { x, y, z }))
+            .collect()
+    }
+
+    /// Heightmap of a chunk column, see `height_at`
+    pub fn heightmap(&self, chunk_x: i32, chunk_z: i32) -> Option<&Vec<i32>> {
+        self.heightmaps.get(&(chunk_x, chunk_z))
+    }
+
+    /// The y above the highest opaque block of a column, `i32::MIN` if there is none loaded
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        match self.heightmaps.get(&(x.div_euclid(CS_I32), z.div_euclid(CS_I32))) {
+            None => { i32::MIN }
+            Some(heightmap) => { heightmap[(x.rem_euclid(CS_I32) + z.rem_euclid(CS_I32) * CS_I32) as usize] }
+        }
+    }
+
+    /// The biome of a column, worlds without biomes are plains everywhere.
+    /// Only the window title reads it for now, grass and leaves aren't tinted by biome yet
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        match &self.biomes {
+            Some(biomes) => { biomes.biome_at(x, z) }
+            None => { Biome::Plains }
+        }
+    }
+
+    /// Block at a world position, `None` if its chunk isn't loaded
+    pub fn get_loaded_block(&self, x: i32, y: i32, z: i32) -> Option<BlockType> {
+        self.chunks.get(&ChunkPosition::containing(x, y, z))
+            .map(|chunk| chunk.blocks[local_index(x, y, z)].block_type)
+    }
+
+    /// Block at a world position, anything that isn't loaded is assumed to be air
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> BlockType {
+        self.get_loaded_block(x, y, z).unwrap_or(BlockType::AIR)
+    }
+
+    /// Fluid level of the block at a world position, 0 for anything that isn't a flowing fluid or isn't loaded
+    pub fn get_level(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.chunks.get(&ChunkPosition::containing(x, y, z))
+            .map_or(0, |chunk| chunk.blocks[local_index(x, y, z)].level)
+    }
+
+    /// Light level of one channel at a world position, 0 if it isn't loaded
+    pub fn get_light(&self, x: i32, y: i32, z: i32, channel: LightChannel) -> u8 {
+        self.chunks.get(&ChunkPosition::containing(x, y, z))
+            .map_or(0, |chunk| chunk.light.get(local_index(x, y, z), channel))
+    }
+
+    /// Whether a point is inside a fluid, below its surface if it is the top block of the fluid
+    pub fn is_submerged(&self, position: Vec3) -> bool {
+        let (x, y, z) = (position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32);
+        let block_type = self.get_block(x, y, z);
+        if !self.registry.is_fluid(block_type) {
+            return false;
+        }
+        self.get_block(x, y + 1, z) == block_type || position.y - (y as f32) < fluid::surface_height(self.get_level(x, y, z))
     }
-}
 
-fn generate_chunk_noise(chunk_x: i32, chunk_z: i32, noise: &FastNoiseLite) -> Chunk {
-    let mut chunk = Chunk::create(vec![Block { block_type: BlockType::AIR }; CS * CS * CS]);
+    /// Changes a block and updates the heightmap and light around it, the block and its neighbours get told about it.
+    /// Does nothing if the chunk isn't loaded
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_type: BlockType) {
+        self.replace_block(x, y, z, Block::new(block_type));
+    }
 
-    if SUPER_FLAT {
-        for x in 0..CS_I32 {
-            for y in 0..4 {
-                for z in 0..CS_I32 {
-                    let index = (x + y * CS_I32 + z * CS_I32 * CS_I32) as usize;
-                    chunk.blocks[index] = Block { block_type: BlockType::DIRT };
+    /// Same as `set_block`, with the level of a fluid. Level 0 is a source
+    pub fn set_fluid(&mut self, x: i32, y: i32, z: i32, block_type: BlockType, level: u8) {
+        self.replace_block(x, y, z, Block { block_type, level });
+    }
+
+    fn replace_block(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        let position = ChunkPosition::containing(x, y, z);
+        let Some(chunk) = self.chunks.get_mut(&position) else { return };
+        let index = local_index(x, y, z);
+        if chunk.blocks[index] == block {
+            return;
+        }
+        chunk.blocks[index] = block;
+        let block_type = block.block_type;
+
+        // blocks on the chunk border are sampled by the neighbour's mesh too, corners included for ao
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(chunk) = self.chunks.get_mut(&ChunkPosition::containing(x + dx, y + dy, z + dz)) {
+                        chunk.dirty = true;
+                    }
                 }
             }
         }
-    } else {
-        for x in 0..CS_I32 {
-            for z in 0..CS_I32 {
-                let height = (noise.get_noise_2d((chunk_x + x) as f32, (chunk_z + z) as f32) + 1.0) / 2.0;
-                // let y = 0;// (height * 32.0) as i32;
-                for y in 0..(height * CS_F32) as i32 {
-                    let index = (x + y * CS_I32 + z * CS_I32 * CS_I32) as usize;
-
-                    let block_type: BlockType;
-                    // if y > (CHUNK_SIZE as i32 / 2) {
-                    //     block_type = BlockType::COBBLESTONE
-                    // } else {
-                    block_type = BlockType::DIRT;
-                    // }
-
-                    chunk.blocks[index] = Block { block_type };
+
+        self.update_height(x, y, z, block_type);
+        light::update_light(self, [x, y, z]);
+        self.notify_neighbours([x, y, z]);
+    }
+
+    /// Runs the `neighbour_changed` behaviour of a block that changed and of its six neighbours
+    fn notify_neighbours(&mut self, [x, y, z]: [i32; 3]) {
+        for [dx, dy, dz] in NEIGHBOURHOOD {
+            let pos = [x + dx, y + dy, z + dz];
+            if let Some(neighbour_changed) = self.registry.behaviour(self.get_block(pos[0], pos[1], pos[2])).neighbour_changed {
+                neighbour_changed(self, pos);
+            }
+        }
+    }
+
+    /// Runs as many ticks as fit into the time that passed, at `TICKS_PER_SECOND`
+    pub fn update(&mut self, delta_time: f32) {
+        let tick_length = 1.0 / TICKS_PER_SECOND;
+        self.tick_accumulator += delta_time;
+
+        let mut ticks = 0;
+        while self.tick_accumulator >= tick_length {
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.tick_accumulator = 0.0;
+                break;
+            }
+            self.tick();
+            self.tick_accumulator -= tick_length;
+            ticks += 1;
+        }
+    }
+
+    /// Advances the world by one tick, runs every scheduled update that is due and then the random ticks
+    pub fn tick(&mut self) {
+        self.tick += 1;
+        for pos in self.scheduled_updates.take_due(self.tick) {
+            if let Some(scheduled_tick) = self.registry.behaviour(self.get_block(pos[0], pos[1], pos[2])).scheduled_tick {
+                scheduled_tick(self, pos);
+            }
+        }
+        self.random_ticks();
+    }
+
+    /// Picks a few random blocks in every section of the chunks around the simulation center.
+    /// Chunks go in position order and the blocks come from a hash of the tick, so it is deterministic
+    fn random_ticks(&mut self) {
+        let center = self.simulation_center;
+        let mut positions: Vec<ChunkPosition> = self.chunks.keys()
+            .filter(|pos| (pos.x - center.x).abs() <= SIMULATION_DISTANCE && (pos.z - center.z).abs() <= SIMULATION_DISTANCE)
+            .copied()
+            .collect();
+        positions.sort_unstable_by_key(|pos| (pos.x, pos.y, pos.z));
+
+        let sections = CS_I32 / SECTION_SIZE;
+        for chunk in positions {
+            for section in 0..sections * sections * sections {
+                let origin = [
+                    chunk.x * CS_I32 + section % sections * SECTION_SIZE,
+                    chunk.y * CS_I32 + section / sections % sections * SECTION_SIZE,
+                    chunk.z * CS_I32 + section / (sections * sections) * SECTION_SIZE,
+                ];
+                for index in 0..RANDOM_TICKS_PER_SECTION {
+                    let random = tick::hash(&[self.tick as i64, origin[0] as i64, origin[1] as i64, origin[2] as i64, index as i64]);
+                    let size = SECTION_SIZE as u64;
+                    let pos = [
+                        origin[0] + (random % size) as i32,
+                        origin[1] + (random / size % size) as i32,
+                        origin[2] + (random / (size * size) % size) as i32,
+                    ];
+                    if let Some(random_tick) = self.registry.behaviour(self.get_block(pos[0], pos[1], pos[2])).random_tick {
+                        random_tick(self, pos);
+                    }
                 }
             }
         }
+    }
+
+    /// Deterministic random number for a block, it changes every tick
+    pub fn random(&self, [x, y, z]: [i32; 3]) -> u64 {
+        tick::hash(&[self.tick as i64, x as i64, y as i64, z as i64])
+    }
+
+    /// Updates the block at `pos` in `delay` ticks, unless it is already waiting for an update
+    pub fn schedule_update(&mut self, pos: [i32; 3], delay: u64) {
+        self.scheduled_updates.schedule(pos, self.tick + delay.max(1));
+    }
 
+    /// Number of blocks waiting for a scheduled update
+    pub fn pending_updates(&self) -> usize {
+        self.scheduled_updates.count()
     }
-    chunk
-}
\ No newline at end of file
+
+    fn update_height(&mut self, x: i32, y: i32, z: i32, block_type: BlockType) {
+        let height = self.height_at(x, z);
+        let new_height = if self.registry.transparency(block_type).is_opaque() {
+            height.max(y + 1)
+        } else if y + 1 == height {
+            // the top block got removed, search downwards through loaded chunks for the next one
+            let mut below = y - 1;
+            loop {
+                match self.get_loaded_block(x, below, z) {
+                    None => { break i32::MIN }
+                    Some(block_type) if self.registry.transparency(block_type).is_opaque() => { break below + 1 }
+                    Some(_) => { below -= 1 }
+                }
+            }
+        } else {
+            height
+        };
+
+        if let Some(heightmap) = self.heightmaps.get_mut(&(x.div_euclid(CS_I32), z.div_euclid(CS_I32))) {
+            heightmap[(x.rem_euclid(CS_I32) + z.rem_euclid(CS_I32) * CS_I32) as usize] = new_height;
+        }
+    }
+}